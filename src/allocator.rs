@@ -5,7 +5,7 @@ pub mod linked_list;
 pub mod fixed_size_block;
 
 pub const HEAP_START: u64 = 0x_4444_4444_0000;
-pub const HEAP_SIZE: u64 = 100 * 1024; // 100 KiB
+pub const HEAP_SIZE: u64 = 1024 * 1024; // 1 MiB
 use x86_64::{
     structures::paging::{
         mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, Size4KiB,
@@ -46,6 +46,11 @@ pub fn init_heap(
     Ok(())
 }
 
+/// Returns a lower bound for the number of heap bytes still available.
+pub fn heap_free() -> usize {
+    ALLOCATOR.lock().free()
+}
+
 // A wrapper around spin::Mutex to permit trait implementations.
 pub struct Locked<A> {
     inner: spin::Mutex<A>,
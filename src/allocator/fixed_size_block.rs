@@ -47,6 +47,13 @@ impl FixedSizeBlockAllocator {
         self.fallback_allocator.init(heap_start, heap_size);
     }
 
+    /// Returns the number of bytes the fallback allocator can still hand out.
+    ///
+    /// Blocks cached in the free lists are not included, so this is a lower bound.
+    pub fn free(&self) -> usize {
+        self.fallback_allocator.free()
+    }
+
     /// Allocates using the fallback allocator.
     fn fallback_alloc(&mut self, layout: Layout) -> *mut u8 {
         match self.fallback_allocator.allocate_first_fit(layout) {
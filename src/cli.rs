@@ -1,141 +1,366 @@
-use omega::keyboard::read_input;
-use omega::print;
+use omega::keyboard::{clear_interrupt, discard_input, interrupted, read_line};
+use omega::line_editor::{Completer, History};
 use omega::println;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 
-use crate::fs::block_device::BlockDevice;
-use crate::fs::file_ops::create_file;
-use crate::fs::file_ops::delete_file;
-use crate::fs::file_ops::read_file;
-use crate::fs::file_ops::write_file;
-use crate::DEVICE;
-
-pub fn cli_loop() {
-    loop {
-        print!("> "); // CLI prompt
-        if let Some(command) = read_input() {
-            match command {
-                "exit" => { println!("Thanks for using OmegaOS"); return; },
-                _ => handle_command(command)
+use crate::fs::vfs::create_file;
+use crate::fs::vfs::list_files;
+use crate::fs::vfs::read_file;
+use crate::fs::vfs::write_file;
+use crate::fs::error::FsError;
+use command::CommandError;
+use io::{Io, Stdin, Stdout};
+use parser::{Connector, Stage};
+use vars::Variables;
+
+pub mod command;   // Contains the Command trait implemented by every shell command
+pub mod registry;  // Contains the registry commands are added to at init and looked up in
+pub mod io;        // Contains the input and output streams commands read from and write to
+pub mod vars;      // Contains the shell variables expanded by the parser
+mod parser;        // Contains the tokenizer and the parser splitting a command line into pipeline stages
+mod builtins;      // Contains the commands that belong to the shell itself, like help and history
+mod script;        // Contains the script interpreter and the sh and source commands
+mod edit;          // Contains the full-screen text editor behind the edit command
+mod regex;         // Contains the simple regular expressions used by grep
+mod text;          // Contains the text-processing commands like grep, sort and hexdump
+mod gfx;           // Contains the gfx command showing the system status in a graphics mode
+
+pub use script::source_file;
+
+/// Registers the commands implemented by the shell.
+pub fn register_commands() {
+    builtins::register_commands();
+    script::register_commands();
+    edit::register_commands();
+    text::register_commands();
+    gfx::register_commands();
+}
+
+// File the command history is kept in between boots
+const HISTORY_FILE: &str = "/.history";
+
+// Exit statuses of failed commands, following the Unix shell conventions
+const STATUS_FAILURE: u8 = 1;     // the command ran and failed
+const STATUS_USAGE: u8 = 2;       // bad arguments or a syntax error
+const STATUS_NOT_FOUND: u8 = 127; // no command with that name
+pub const STATUS_INTERRUPTED: u8 = 130; // stopped by Ctrl+C
+
+/// State shared by the shell and the commands it runs.
+pub struct Shell {
+    pub history: History,
+    pub vars: Variables,
+    pub status: u8,         // exit status of the last command line, expanded by `$?`
+    pub exit: Option<u8>,   // set by `exit` to end the shell or script with that status
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        Shell { history: History::new(), vars: Variables::new(), status: 0, exit: None }
+    }
+
+    /// The CLI prompt. Setting `PROMPT_STATUS=1` adds the status of a failed command to it.
+    fn prompt(&self) -> String {
+        if self.status != 0 && self.vars.get("PROMPT_STATUS") == Some("1") {
+            format!("[{}] > ", self.status)
+        } else {
+            String::from("> ")
+        }
+    }
+
+    /// Returns the value a `$name` expands to.
+    fn lookup(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(format!("{}", self.status)),
+            _ => self.vars.get(name).map(String::from),
+        }
+    }
+}
+
+/// Completes the first word of a line as a command and later words as file paths.
+struct ShellCompleter;
+
+impl Completer for ShellCompleter {
+    fn complete(&self, before: &str, word: &str) -> Vec<String> {
+        if before.trim().is_empty() {
+            let mut names: Vec<String> = registry::commands()
+                .into_iter()
+                .flat_map(|command| core::iter::once(command.name()).chain(command.aliases().iter().copied()))
+                .filter(|name| name.starts_with(word))
+                .map(String::from)
+                .collect();
+            names.sort();
+            return names;
+        }
+        // Paths are completed within the filesystem mounted at the part up to the last '/'
+        let (dir, prefix) = match word.rfind('/') {
+            Some(slash) => word.split_at(slash + 1),
+            None => ("", word),
+        };
+        let mut candidates: Vec<String> = list_files(if dir.is_empty() { "/" } else { dir })
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| format!("{}{}", dir, name))
+            .collect();
+        candidates.sort();
+        candidates
+    }
+}
+
+/// Runs the interactive shell until `exit`, sleeping between key presses.
+pub async fn cli_loop(shell: &mut Shell) {
+    shell.history = load_history().await;
+    while shell.exit.is_none() {
+        let prompt = shell.prompt();
+        if let Some(line) = read_line(&prompt, &shell.history, &ShellCompleter).await {
+            let command = match expand_history(&line, &shell.history) {
+                Ok(command) => command,
+                Err(event) => {
+                    println!("{}: event not found", event);
+                    continue;
+                }
+            };
+            if command != line {
+                println!("{}", command); // show what `!!` or `!n` expanded to
+            }
+            shell.history.push(&command);
+            clear_interrupt(); // a Ctrl+C at the prompt only cancelled that line
+            handle_command(&command, shell, &mut Io::console()).await;
+            if interrupted() {
+                // Keys typed while the command ran, the Ctrl+C among them, are dropped
+                discard_input();
+                clear_interrupt();
             }
         }
     }
+    save_history(&shell.history).await;
+    println!("Thanks for using OmegaOS");
 }
 
-/// Parses the given command string and executes the corresponding action.
-fn handle_command(command: &str) {
-    let parts: Vec<&str> = command.trim().split_whitespace().collect();
-
-    if let Some(mut device_lock) = DEVICE.try_lock() {
-        if let Some(device) = &mut *device_lock {
-            if let Some(cmd) = parts.first() {
-                match *cmd {
-                    "help" => {
-                        if parts.len() != 1 {
-                            println!("Incorrect amount of parameters.");
-                            println!("Did you mean 'help'?");
-                            return;
-                        }
-                        println!("Available commands: touch <file>, rm <file>, wf <file>, ls, cat <file>, echo, help, exit");
-                    }
-                    "echo" =>
-                    {
-                        if parts.len() < 2 {
-                            println!("Usage: echo <text>");
-                            return;
-                        }
-                        
-                        let text = parts[1..].join(" ");
-                        println!("{}", text);
-                    }
-                    "touch" => {
-                        if parts.len() != 2 {
-                            println!("Incorrect amount of parameters.");
-                            return;
-                        }
-                        if let Some(filename) = parts.get(1) {
-                            create_file(device, filename);
-                        } else {
-                            println!("Usage: touch <filename>");
-                        }
-                    }
-                    "wf" => {
-                            if parts.len() != 2 {
-                                println!("Incorrect amount of parameters.");
-                                return;
-                            }
-                            // Create a fixed-size local buffer for the filename
-                            let original_filename = parts[1];
-                            let mut filename_buf = [0u8; 64];
-                            let len = original_filename.len().min(filename_buf.len());
-                            filename_buf[..len].copy_from_slice(original_filename.as_bytes());
-                            println!("Enter data for file:");
-                            if let Some(input) = read_input() {
-                                // Create a new string slice from the buffer
-                                if let Ok(safe_filename) = core::str::from_utf8(&filename_buf[..len]) {
-                                    write_file(device, safe_filename, input.as_bytes());
-                                }
-                            } else {
-                                println!("No data entered!");
-                            }
-                        }
-                    "rm" => {
-                        if parts.len() != 2 {
-                            println!("Incorrect amount of parameters.");
-                            return;
-                        }
-                        if let Some(filename) = parts.get(1) {
-                            println!("Removing file: {}", filename);
-                            delete_file(device, filename);
-                        } else {
-                            println!("Usage: rm <filename>");
-                        }
-                    }
-                    "cat" => {
-                        if parts.len() != 2 {
-                            println!("Incorrect amount of parameters.");
-                            return;
-                        }
-                        if let Some(filename) = parts.get(1) {
-                            if let Some(data) = read_file(device, filename) {
-                                if let Ok(text) = core::str::from_utf8(&data) {
-                                    println!("{}", text);
-                                } else {
-                                    println!("File content is not valid UTF-8");
-                                }
-                            } else {
-                                println!("File not found!");
-                            }
-                        } else {
-                            println!("Usage: cat <filename>");
-                        }
-                    }
-                    "ls" => {
-                        if parts.len() != 1 {
-                            println!("Incorrect amount of parameters.");
-                            println!("Did you mean 'ls'?");
-                            return;
-                        }
-
-                        let file_table = device.get_file_table().lock();
-
-                        let files = file_table.list_files();
-                        if files.is_empty() {
-                            println!("No files found.");
-                        } else {
-                            println!("Files:");
-                            for file in files {
-                                println!("- {}", file);
-                            }
-                        }
-                    },
-                    _ => println!("Unknown command: {}", command)
+/// Reads the history saved by a previous session, one command per line.
+async fn load_history() -> History {
+    let mut history = History::new();
+    if let Ok(data) = read_file(HISTORY_FILE).await {
+        for line in String::from_utf8_lossy(&data).lines() {
+            history.push(line);
+        }
+    }
+    history
+}
+
+/// Writes the history out for the next session; called once, when the shell exits.
+async fn save_history(history: &History) {
+    let mut data = String::new();
+    for (_, line) in history.iter() {
+        data.push_str(line);
+        data.push('\n');
+    }
+    // Failing to save only costs persistence
+    let _ = write_or_create(HISTORY_FILE, data.as_bytes()).await;
+}
+
+/// Replaces the contents of `path` with `data`, creating the file if it does not exist.
+async fn write_or_create(path: &str, data: &[u8]) -> Result<(), FsError> {
+    match write_file(path, data).await {
+        Err(FsError::NotFound) => {
+            create_file(path)?;
+            write_file(path, data).await
+        }
+        result => result,
+    }
+}
+
+/// Replaces `!!` with the previous command and `!n` with history entry `n`.
+/// Like in sh, a `!` inside single quotes is left alone.
+///
+/// Fails with the event that could not be found.
+fn expand_history<'a>(line: &'a str, history: &History) -> Result<String, &'a str> {
+    let mut expanded = String::new();
+    let mut rest = line;
+    let mut quote = None;
+    while let Some(start) = find_event(rest, &mut quote) {
+        expanded.push_str(&rest[..start]);
+        let event = &rest[start..];
+        if let Some(after) = event.strip_prefix("!!") {
+            expanded.push_str(history.last().ok_or("!!")?);
+            rest = after;
+            continue;
+        }
+        let digits = event[1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(event.len() - 1);
+        if digits == 0 {
+            // A lone `!` is kept as it is
+            expanded.push('!');
+            rest = &event[1..];
+            continue;
+        }
+        let number = &event[..=digits];
+        let entry = number[1..].parse().ok().and_then(|n| history.get(n)).ok_or(number)?;
+        expanded.push_str(entry);
+        rest = &event[digits + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Finds the next `!` in `text` that is not inside single quotes.
+///
+/// `quote` is the quote left open by the text scanned before, and is updated
+/// up to the returned position.
+fn find_event(text: &str, quote: &mut Option<char>) -> Option<usize> {
+    for (index, c) in text.char_indices() {
+        match (c, *quote) {
+            ('!', None | Some('"')) => return Some(index),
+            ('\'', None) | ('"', None) => *quote = Some(c),
+            (c, Some(open)) if c == open => *quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Runs a command line: pipelines joined by `&&`, `||` and `;`.
+///
+/// A pipeline skipped by `&&` or `||` leaves the status as it was, so
+/// `a && b || c` runs `c` when either `a` or `b` fails.
+async fn handle_command(line: &str, shell: &mut Shell, io: &mut Io) {
+    let list = match parser::split_list(line) {
+        Ok(list) => list,
+        Err(e) => {
+            println!("{}", e);
+            shell.status = STATUS_USAGE;
+            return;
+        }
+    };
+    for (connector, pipeline) in list {
+        if shell.exit.is_some() || check_interrupt(shell) {
+            return;
+        }
+        let run = match connector {
+            Connector::Always => true,
+            Connector::And => shell.status == 0,
+            Connector::Or => shell.status != 0,
+        };
+        if run {
+            run_pipeline(pipeline, shell, io).await;
+        }
+    }
+}
+
+/// Whether Ctrl+C was pressed, in which case the shell stops what it runs with status 130.
+fn check_interrupt(shell: &mut Shell) -> bool {
+    if interrupted() {
+        shell.status = STATUS_INTERRUPTED;
+    }
+    interrupted()
+}
+
+/// Parses and runs a single pipeline.
+///
+/// Stages run one after the other; each stage's output is collected in an
+/// in-memory pipe and becomes the input of the next one. The status of the
+/// last stage becomes the status of the pipeline.
+async fn run_pipeline(line: &str, shell: &mut Shell, io: &mut Io) {
+    let stages = match parser::parse(line, &|name| shell.lookup(name)) {
+        Ok(stages) => stages,
+        Err(e) => {
+            println!("{}", e);
+            shell.status = STATUS_USAGE;
+            return;
+        }
+    };
+    if stages.is_empty() {
+        return; // a blank line keeps the previous status
+    }
+    let mut piped: Option<Vec<u8>> = None;
+    for (index, stage) in stages.iter().enumerate() {
+        if check_interrupt(shell) {
+            return;
+        }
+        let last = index + 1 == stages.len();
+        let stdin = match &stage.input {
+            Some(path) => match read_file(path).await {
+                Ok(data) => Stdin::Data(data),
+                Err(e) => {
+                    println!("{}: {}", path, e);
+                    shell.status = STATUS_FAILURE;
+                    return;
                 }
+            },
+            None => piped.take().map_or_else(|| io.stdin.take(), Stdin::Data),
+        };
+        let console = last && stage.output.is_none() && matches!(io.stdout, Stdout::Console);
+        let stdout = if console { Stdout::Console } else { Stdout::Pipe(Vec::new()) };
+        let mut stage_io = Io { stdin, stdout };
+
+        // A failing stage still passes on whatever it wrote, like a Unix pipeline
+        let mut status = run_stage(stage, shell, &mut stage_io).await;
+        let output = stage_io.stdout.take();
+        if let Some(redirect) = &stage.output {
+            if let Err(e) = redirect_output(&redirect.path, redirect.append, &output).await {
+                println!("{}: {}", redirect.path, e);
+                status = STATUS_FAILURE;
             }
+            // Redirected output is not piped on, so the next stage sees empty input
+            piped = Some(Vec::new());
+        } else if last {
+            io.stdout.write_bytes(&output);
         } else {
-            println!("Device is not initialized.");
+            piped = Some(output);
         }
+        shell.status = status;
+    }
+}
+
+/// Runs the registered command a single pipeline stage names, printing its error
+/// if it fails, and returns its exit status.
+async fn run_stage(stage: &Stage, shell: &mut Shell, io: &mut Io) -> u8 {
+    let args: Vec<&str> = stage.args.iter().map(String::as_str).collect();
+    let (name, args) = args.split_first().expect("the parser never yields empty stages");
+    let Some(command) = registry::find(name) else {
+        println!("Unknown command: {}", name);
+        return STATUS_NOT_FOUND;
+    };
+    let result = if command.args().accepts(args.len()) {
+        command.run(shell, io, args).await
     } else {
-        println!("Device is already locked");
+        Err(CommandError::Usage)
+    };
+    match result {
+        Ok(()) => 0,
+        Err(CommandError::Usage) => {
+            println!("Usage: {}", command.usage());
+            STATUS_USAGE
+        }
+        Err(CommandError::Status(status)) => status,
+        Err(CommandError::Interrupted) => STATUS_INTERRUPTED,
+        Err(e) => {
+            println!("{}", e);
+            STATUS_FAILURE
+        }
+    }
+}
+
+/// Writes a stage's output to `path`, after the existing contents when appending.
+async fn redirect_output(path: &str, append: bool, output: &[u8]) -> Result<(), FsError> {
+    if !append {
+        return write_or_create(path, output).await;
     }
+    let mut data = match read_file(path).await {
+        Ok(data) => data,
+        Err(FsError::NotFound) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    data.extend_from_slice(output);
+    write_or_create(path, &data).await
+}
+
+#[test_case]
+fn test_expand_history_skips_single_quotes() {
+    let mut history = History::new();
+    history.push("ls");
+    assert_eq!(expand_history("echo !! '!!' \"!1\"", &history).as_deref(), Ok("echo ls '!!' \"ls\""));
+    assert_eq!(expand_history("echo \"it's\" !!", &history).as_deref(), Ok("echo \"it's\" ls"));
+    assert_eq!(expand_history("echo !7", &history), Err("!7"));
 }
@@ -0,0 +1,328 @@
+use alloc::boxed::Box;
+use core::fmt::Write;
+use omega::keyboard::keymap::{Keymap as KeymapFile, Layout};
+use omega::keyboard::{keymap_name, set_keymap};
+use omega::vga_buffer::{CursorShape, WRITER};
+use x86_64::instructions::interrupts;
+use crate::cli::Shell;
+use crate::cli::command::{parse_options, Args, Command, CommandError};
+use crate::cli::io::Io;
+use crate::cli::registry::{self, register};
+use crate::cli::vars::is_valid_name;
+use crate::fs::error::FsError;
+use crate::fs::vfs::read_file;
+
+/// Registers the commands that belong to the shell itself.
+pub fn register_commands() {
+    register(&Help);
+    register(&Echo);
+    register(&History);
+    register(&Exit);
+    register(&True);
+    register(&False);
+    register(&Test);
+    register(&Set);
+    register(&Unset);
+    register(&Export);
+    register(&Env);
+    register(&Keymap);
+    register(&Cursor);
+}
+
+/// Splits a `NAME=value` argument, checking the name.
+fn parse_assignment(arg: &str) -> Result<(&str, Option<&str>), CommandError> {
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (arg, None),
+    };
+    if !is_valid_name(name) {
+        return Err(CommandError::Other(alloc::format!("Invalid variable name: {}", name)));
+    }
+    Ok((name, value))
+}
+
+struct Help;
+
+impl Command for Help {
+    fn name(&self) -> &'static str { "help" }
+    fn aliases(&self) -> &'static [&'static str] { &["?"] }
+    fn usage(&self) -> &'static str { "help [command]" }
+    fn help(&self) -> &'static str { "List the commands, or describe one of them" }
+    fn args(&self) -> Args { Args::Range(0, 1) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let Some(name) = args.first() else {
+            writeln!(io.stdout, "Available commands:")?;
+            for command in registry::commands() {
+                writeln!(io.stdout, "  {:<28} {}", command.usage(), command.help())?;
+            }
+            return Ok(());
+        };
+        let command = registry::find(name)
+            .ok_or_else(|| CommandError::Other(alloc::format!("No such command: {}", name)))?;
+        writeln!(io.stdout, "Usage: {}", command.usage())?;
+        if !command.aliases().is_empty() {
+            writeln!(io.stdout, "Aliases: {}", command.aliases().join(", "))?;
+        }
+        writeln!(io.stdout, "{}", command.help())?;
+        Ok(())
+    }
+}
+
+struct Echo;
+
+impl Command for Echo {
+    fn name(&self) -> &'static str { "echo" }
+    fn usage(&self) -> &'static str { "echo <text>" }
+    fn help(&self) -> &'static str { "Print the given text" }
+    fn args(&self) -> Args { Args::AtLeast(1) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        writeln!(io.stdout, "{}", args.join(" "))?;
+        Ok(())
+    }
+}
+
+struct History;
+
+impl Command for History {
+    fn name(&self) -> &'static str { "history" }
+    fn usage(&self) -> &'static str { "history" }
+    fn help(&self) -> &'static str { "List the previous commands; recall one with !n or !!" }
+    fn args(&self) -> Args { Args::Exactly(0) }
+
+    async fn run(&self, shell: &mut Shell, io: &mut Io, _args: &[&str]) -> Result<(), CommandError> {
+        for (number, line) in shell.history.iter() {
+            writeln!(io.stdout, "{:>4}  {}", number, line)?;
+        }
+        Ok(())
+    }
+}
+
+struct Exit;
+
+impl Command for Exit {
+    fn name(&self) -> &'static str { "exit" }
+    fn aliases(&self) -> &'static [&'static str] { &["quit"] }
+    fn usage(&self) -> &'static str { "exit [status]" }
+    fn help(&self) -> &'static str { "Leave the shell or script, by default with the status of the last command" }
+    fn args(&self) -> Args { Args::Range(0, 1) }
+
+    async fn run(&self, shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let status = match args.first() {
+            Some(status) => status.parse().map_err(|_| CommandError::Usage)?,
+            None => shell.status,
+        };
+        shell.exit = Some(status);
+        Ok(())
+    }
+}
+
+struct True;
+
+impl Command for True {
+    fn name(&self) -> &'static str { "true" }
+    fn usage(&self) -> &'static str { "true" }
+    fn help(&self) -> &'static str { "Do nothing, successfully" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, _args: &[&str]) -> Result<(), CommandError> {
+        Ok(())
+    }
+}
+
+struct False;
+
+impl Command for False {
+    fn name(&self) -> &'static str { "false" }
+    fn usage(&self) -> &'static str { "false" }
+    fn help(&self) -> &'static str { "Do nothing, unsuccessfully" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, _args: &[&str]) -> Result<(), CommandError> {
+        Err(CommandError::Status(1))
+    }
+}
+
+struct Test;
+
+impl Command for Test {
+    fn name(&self) -> &'static str { "test" }
+    fn aliases(&self) -> &'static [&'static str] { &["["] }
+    fn usage(&self) -> &'static str { "test <expression>" }
+    fn help(&self) -> &'static str {
+        "Check an expression: -e/-f <file>, -n/-z <text>, a = b, a != b, n -eq/-ne/-lt/-le/-gt/-ge m, ! expression"
+    }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        // `[` is the same command, but wants a closing `]`
+        let args = match args.split_last() {
+            Some((&"]", expression)) => expression,
+            _ => args,
+        };
+        if evaluate(args).await? { Ok(()) } else { Err(CommandError::Status(1)) }
+    }
+}
+
+/// Evaluates the expression of `test`.
+async fn evaluate(args: &[&str]) -> Result<bool, CommandError> {
+    let number = |arg: &str| arg.parse::<i64>().map_err(|_| CommandError::Other(alloc::format!("Integer expected: {}", arg)));
+    Ok(match *args {
+        [] => false,
+        ["!", ref rest @ ..] => !Box::pin(evaluate(rest)).await?,
+        [text] => !text.is_empty(),
+        ["-n", text] => !text.is_empty(),
+        ["-z", text] => text.is_empty(),
+        ["-e" | "-f", path] => !matches!(read_file(path).await, Err(FsError::NotFound)),
+        [a, "=", b] => a == b,
+        [a, "!=", b] => a != b,
+        [a, "-eq", b] => number(a)? == number(b)?,
+        [a, "-ne", b] => number(a)? != number(b)?,
+        [a, "-lt", b] => number(a)? < number(b)?,
+        [a, "-le", b] => number(a)? <= number(b)?,
+        [a, "-gt", b] => number(a)? > number(b)?,
+        [a, "-ge", b] => number(a)? >= number(b)?,
+        _ => return Err(CommandError::Usage),
+    })
+}
+
+struct Set;
+
+impl Command for Set {
+    fn name(&self) -> &'static str { "set" }
+    fn usage(&self) -> &'static str { "set [NAME=value...]" }
+    fn help(&self) -> &'static str { "Set shell variables, or list them all" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        if args.is_empty() {
+            for (name, value) in shell.vars.iter(false) {
+                writeln!(io.stdout, "{}={}", name, value)?;
+            }
+        }
+        for arg in args {
+            let (name, value) = parse_assignment(arg)?;
+            shell.vars.set(name, value.ok_or(CommandError::Usage)?);
+        }
+        Ok(())
+    }
+}
+
+struct Unset;
+
+impl Command for Unset {
+    fn name(&self) -> &'static str { "unset" }
+    fn usage(&self) -> &'static str { "unset <NAME...>" }
+    fn help(&self) -> &'static str { "Remove shell variables" }
+    fn args(&self) -> Args { Args::AtLeast(1) }
+
+    async fn run(&self, shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        for name in args {
+            shell.vars.unset(name);
+        }
+        Ok(())
+    }
+}
+
+struct Export;
+
+impl Command for Export {
+    fn name(&self) -> &'static str { "export" }
+    fn usage(&self) -> &'static str { "export [NAME[=value]...]" }
+    fn help(&self) -> &'static str { "Pass variables on to scripts the shell runs, or list exported ones" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        if args.is_empty() {
+            for (name, value) in shell.vars.iter(true) {
+                writeln!(io.stdout, "export {}={}", name, value)?;
+            }
+        }
+        for arg in args {
+            let (name, value) = parse_assignment(arg)?;
+            if let Some(value) = value {
+                shell.vars.set(name, value);
+            }
+            shell.vars.export(name);
+        }
+        Ok(())
+    }
+}
+
+struct Env;
+
+impl Command for Env {
+    fn name(&self) -> &'static str { "env" }
+    fn usage(&self) -> &'static str { "env" }
+    fn help(&self) -> &'static str { "List the exported variables" }
+    fn args(&self) -> Args { Args::Exactly(0) }
+
+    async fn run(&self, shell: &mut Shell, io: &mut Io, _args: &[&str]) -> Result<(), CommandError> {
+        for (name, value) in shell.vars.iter(true) {
+            writeln!(io.stdout, "{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+struct Keymap;
+
+impl Command for Keymap {
+    fn name(&self) -> &'static str { "keymap" }
+    fn usage(&self) -> &'static str { "keymap [-l] [-f file] [layout]" }
+    fn help(&self) -> &'static str { "Show or switch the keyboard layout; -l lists layouts, -f loads a keymap file" }
+    fn args(&self) -> Args { Args::Range(0, 2) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "l", "f")?;
+        if options.has('l') {
+            for layout in Layout::ALL {
+                writeln!(io.stdout, "{}", layout.name())?;
+            }
+            return Ok(());
+        }
+        let keymap = match (options.value('f'), options.operands.as_slice()) {
+            (None, []) => {
+                writeln!(io.stdout, "{}", keymap_name())?;
+                return Ok(());
+            }
+            (None, [name]) => {
+                let layout = Layout::from_name(name)
+                    .ok_or_else(|| CommandError::Other(alloc::format!("Unknown layout: {} (try keymap -l)", name)))?;
+                KeymapFile::new(layout)
+            }
+            (Some(path), []) => {
+                let data = read_file(path).await?;
+                KeymapFile::parse(path, &alloc::string::String::from_utf8_lossy(&data))
+                    .map_err(|e| CommandError::Other(alloc::format!("{}: {}", path, e)))?
+            }
+            _ => return Err(CommandError::Usage),
+        };
+        set_keymap(keymap);
+        Ok(())
+    }
+}
+
+struct Cursor;
+
+impl Command for Cursor {
+    fn name(&self) -> &'static str { "cursor" }
+    fn usage(&self) -> &'static str { "cursor <underline|block|on|off>" }
+    fn help(&self) -> &'static str { "Change the shape of the console cursor, or show or hide it" }
+    fn args(&self) -> Args { Args::Exactly(1) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        interrupts::without_interrupts(|| {
+            let mut writer = WRITER.lock();
+            match args[0] {
+                "underline" => writer.set_cursor_shape(CursorShape::Underline),
+                "block" => writer.set_cursor_shape(CursorShape::Block),
+                "on" => writer.set_cursor_visible(true),
+                "off" => writer.set_cursor_visible(false),
+                _ => return Err(CommandError::Usage),
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,178 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use crate::cli::Shell;
+use crate::cli::io::Io;
+use crate::fs::error::FsError;
+
+/// How many arguments a command accepts, not counting its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Args {
+    Exactly(usize),
+    Range(usize, usize), // inclusive bounds
+    AtLeast(usize),
+}
+
+impl Args {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Args::Exactly(n) => count == n,
+            Args::Range(min, max) => (min..=max).contains(&count),
+            Args::AtLeast(min) => count >= min,
+        }
+    }
+}
+
+/// Errors a command can fail with.
+#[derive(Debug)]
+pub enum CommandError {
+    Usage,          // the arguments do not match what the command expects
+    Fs(FsError),    // a filesystem operation failed
+    Other(String),  // any other failure, already phrased for the user
+    Status(u8),     // a quiet failure with this exit status, like `false`
+    Interrupted,    // stopped by Ctrl+C
+}
+
+impl From<FsError> for CommandError {
+    fn from(error: FsError) -> Self {
+        CommandError::Fs(error)
+    }
+}
+
+// Writing to the console or a pipe cannot fail, but `write!` still returns a `Result`
+impl From<fmt::Error> for CommandError {
+    fn from(_: fmt::Error) -> Self {
+        CommandError::Other(String::from("Write error"))
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Usage => f.write_str("Incorrect parameters"),
+            CommandError::Fs(error) => write!(f, "{}", error),
+            CommandError::Other(message) => f.write_str(message),
+            CommandError::Status(status) => write!(f, "Exit status {}", status),
+            CommandError::Interrupted => f.write_str("Interrupted"),
+        }
+    }
+}
+
+/// A shell command that can be added to the registry.
+///
+/// The shell checks the argument count against `args` before calling `run`,
+/// and prints `usage` whenever a command fails with `CommandError::Usage`.
+pub trait Command: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Other names the command can be invoked by.
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The command line synopsis, e.g. `cat <file>`.
+    fn usage(&self) -> &'static str;
+
+    /// A one-line description shown by `help`.
+    fn help(&self) -> &'static str;
+
+    fn args(&self) -> Args;
+
+    /// Runs the command with its arguments, the command name excluded.
+    ///
+    /// Output goes to `io.stdout` so it can be redirected or piped; errors are
+    /// returned and printed on the console by the shell. A command waiting for keys
+    /// or files sleeps on the executor like the shell does at its prompt.
+    async fn run(&self, shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError>;
+}
+
+/// The future of a running command, boxed so the registry can hold commands of any type.
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = Result<(), CommandError>> + 'a>>;
+
+/// A `Command` as kept in the registry, where `run` cannot be an `async fn`.
+pub trait DynCommand: Sync {
+    fn name(&self) -> &'static str;
+    fn aliases(&self) -> &'static [&'static str];
+    fn usage(&self) -> &'static str;
+    fn help(&self) -> &'static str;
+    fn args(&self) -> Args;
+    fn run<'a>(&'a self, shell: &'a mut Shell, io: &'a mut Io, args: &'a [&'a str]) -> CommandFuture<'a>;
+}
+
+impl<C: Command> DynCommand for C {
+    fn name(&self) -> &'static str { Command::name(self) }
+    fn aliases(&self) -> &'static [&'static str] { Command::aliases(self) }
+    fn usage(&self) -> &'static str { Command::usage(self) }
+    fn help(&self) -> &'static str { Command::help(self) }
+    fn args(&self) -> Args { Command::args(self) }
+
+    fn run<'a>(&'a self, shell: &'a mut Shell, io: &'a mut Io, args: &'a [&'a str]) -> CommandFuture<'a> {
+        Box::pin(Command::run(self, shell, io, args))
+    }
+}
+
+/// Command line options split from the operands, as parsed by `parse_options`.
+pub struct Options<'a> {
+    flags: Vec<char>,
+    values: Vec<(char, &'a str)>,
+    pub operands: Vec<&'a str>,
+}
+
+impl<'a> Options<'a> {
+    pub fn has(&self, flag: char) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// The value given to `option`, if it was used; the last one wins.
+    pub fn value(&self, option: char) -> Option<&'a str> {
+        self.values.iter().rev().find(|(name, _)| *name == option).map(|(_, value)| *value)
+    }
+
+    /// The value of `option` as a number, or `default` if it was not used.
+    pub fn number(&self, option: char, default: usize) -> Result<usize, CommandError> {
+        match self.value(option) {
+            Some(value) => value.parse().map_err(|_| CommandError::Other(format!("Invalid number: {}", value))),
+            None => Ok(default),
+        }
+    }
+}
+
+/// Splits `args` into single-letter options and operands.
+///
+/// `flags` lists the options that stand alone and may be combined (`-iv`),
+/// `with_value` those that take a value (`-n 5` or `-n5`). Options end at the
+/// first operand or at `--`; a lone `-` is an operand.
+pub fn parse_options<'a>(args: &[&'a str], flags: &str, with_value: &str) -> Result<Options<'a>, CommandError> {
+    let mut options = Options { flags: Vec::new(), values: Vec::new(), operands: Vec::new() };
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        let Some(letters) = arg.strip_prefix('-').filter(|letters| !letters.is_empty()) else {
+            options.operands.push(arg);
+            break;
+        };
+        for (index, letter) in letters.char_indices() {
+            if flags.contains(letter) {
+                options.flags.push(letter);
+            } else if with_value.contains(letter) {
+                let rest = &letters[index + letter.len_utf8()..];
+                let value = match rest {
+                    "" => *args.next().ok_or(CommandError::Usage)?,
+                    _ => rest,
+                };
+                options.values.push((letter, value));
+                break;
+            } else {
+                return Err(CommandError::Other(format!("Unknown option: -{}", letter)));
+            }
+        }
+    }
+    options.operands.extend(args);
+    Ok(options)
+}
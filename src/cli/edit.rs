@@ -0,0 +1,397 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use omega::keyboard::{clear_interrupt, read_key};
+use omega::vga_buffer::{cp437, Color, BUFFER_HEIGHT, BUFFER_WIDTH, WRITER};
+use pc_keyboard::{DecodedKey, KeyCode};
+use x86_64::instructions::interrupts;
+use crate::cli::{write_or_create, Shell};
+use crate::cli::command::{Args, Command, CommandError};
+use crate::cli::io::Io;
+use crate::cli::registry::register;
+use crate::fs::error::FsError;
+use crate::fs::vfs::read_file;
+
+pub fn register_commands() {
+    register(&Edit);
+}
+
+// The last screen row is the status line
+const TEXT_ROWS: usize = BUFFER_HEIGHT - 1;
+const TAB_WIDTH: usize = 4;
+
+/// One character of a line as it was in the file.
+#[derive(Clone, Copy, PartialEq)]
+enum Symbol {
+    Char(char),
+    Raw(u8), // a byte that is not valid UTF-8, written back unchanged
+}
+
+/// What the editor is doing with the keys it reads.
+enum Mode {
+    Edit,
+    Search(String), // typing a search query on the status line
+}
+
+/// A full-screen editor for a single file.
+///
+/// The lines keep the bytes of the file as they are: tabs are only expanded on
+/// screen, and saving without changes writes back exactly what was loaded. Like
+/// in nano, a file that ends with a newline shows an empty last line.
+struct Editor<'a> {
+    path: &'a str,
+    lines: Vec<Vec<Symbol>>,
+    row: usize,      // cursor line
+    col: usize,      // cursor character within the line
+    top: usize,      // first line on screen
+    left: usize,     // first screen column shown
+    modified: bool,
+    quit_armed: bool, // Ctrl+Q was pressed once with unsaved changes
+    last_search: String,
+    message: String, // shown on the status line until the next key
+    mode: Mode,
+}
+
+impl<'a> Editor<'a> {
+    async fn open(path: &'a str) -> Result<Self, FsError> {
+        let (data, message) = match read_file(path).await {
+            Ok(data) => (data, String::new()),
+            Err(FsError::NotFound) => (Vec::new(), String::from("New file")),
+            Err(e) => return Err(e),
+        };
+        let lines = data.split(|&byte| byte == b'\n').map(decode_line).collect();
+        Ok(Editor {
+            path,
+            lines,
+            row: 0,
+            col: 0,
+            top: 0,
+            left: 0,
+            modified: false,
+            quit_armed: false,
+            last_search: String::new(),
+            message,
+            mode: Mode::Edit,
+        })
+    }
+
+    /// Reads and applies keys until the user quits.
+    async fn run(&mut self) {
+        loop {
+            self.render();
+            let key = read_key().await;
+            self.message.clear();
+            let quit = match core::mem::replace(&mut self.mode, Mode::Edit) {
+                Mode::Edit => self.handle_key(key).await,
+                Mode::Search(query) => {
+                    self.handle_search_key(key, query);
+                    false
+                }
+            };
+            if quit {
+                return;
+            }
+        }
+    }
+
+    /// Applies one key in editing mode. Returns `true` when the editor should close.
+    async fn handle_key(&mut self, key: DecodedKey) -> bool {
+        let quit_armed = core::mem::replace(&mut self.quit_armed, false);
+        match key {
+            DecodedKey::Unicode('\x11') => { // Ctrl+Q
+                if !self.modified || quit_armed {
+                    return true;
+                }
+                self.quit_armed = true;
+                self.message = String::from("Unsaved changes; press Ctrl+Q again to quit without saving");
+            }
+            DecodedKey::Unicode('\x13') => self.save().await, // Ctrl+S
+            DecodedKey::Unicode('\x06') => self.mode = Mode::Search(String::new()), // Ctrl+F
+            DecodedKey::Unicode('\n') => self.split_line(),
+            DecodedKey::Unicode('\x08') => self.delete_before_cursor(),
+            DecodedKey::Unicode('\x7f') | DecodedKey::RawKey(KeyCode::Delete) => self.delete_at_cursor(),
+            DecodedKey::Unicode('\t') => self.insert('\t'),
+            DecodedKey::RawKey(KeyCode::ArrowLeft) => {
+                if self.col > 0 {
+                    self.col -= 1;
+                } else if self.row > 0 {
+                    self.row -= 1;
+                    self.col = self.lines[self.row].len();
+                }
+            }
+            DecodedKey::RawKey(KeyCode::ArrowRight) => {
+                if self.col < self.lines[self.row].len() {
+                    self.col += 1;
+                } else if self.row + 1 < self.lines.len() {
+                    self.row += 1;
+                    self.col = 0;
+                }
+            }
+            DecodedKey::RawKey(KeyCode::ArrowUp) => self.move_rows(-1),
+            DecodedKey::RawKey(KeyCode::ArrowDown) => self.move_rows(1),
+            DecodedKey::RawKey(KeyCode::PageUp) => self.move_rows(-(TEXT_ROWS as isize)),
+            DecodedKey::RawKey(KeyCode::PageDown) => self.move_rows(TEXT_ROWS as isize),
+            DecodedKey::RawKey(KeyCode::Home) | DecodedKey::Unicode('\x01') => self.col = 0,
+            DecodedKey::RawKey(KeyCode::End) | DecodedKey::Unicode('\x05') => self.col = self.lines[self.row].len(),
+            DecodedKey::Unicode(character) if !character.is_control() => self.insert(character),
+            _ => {}
+        }
+        false
+    }
+
+    /// Applies one key while a search query is typed on the status line.
+    fn handle_search_key(&mut self, key: DecodedKey, mut query: String) {
+        match key {
+            DecodedKey::Unicode('\n') => {
+                // An empty query repeats the previous search
+                if !query.is_empty() {
+                    self.last_search = query;
+                }
+                self.find_next();
+            }
+            DecodedKey::Unicode('\x1b') | DecodedKey::Unicode('\x07') => {} // Esc or Ctrl+G cancels
+            DecodedKey::Unicode('\x08') => {
+                query.pop();
+                self.mode = Mode::Search(query);
+            }
+            DecodedKey::Unicode(character) if !character.is_control() => {
+                query.push(character);
+                self.mode = Mode::Search(query);
+            }
+            _ => self.mode = Mode::Search(query),
+        }
+    }
+
+    /// Moves the cursor to the next match of `last_search` after it, wrapping around the end.
+    fn find_next(&mut self) {
+        let needle: Vec<Symbol> = self.last_search.chars().map(Symbol::Char).collect();
+        if needle.is_empty() {
+            return;
+        }
+        let count = self.lines.len();
+        for step in 0..=count {
+            let row = (self.row + step) % count;
+            // On the cursor line, only matches after the cursor count until the search wraps
+            let from = match step {
+                0 => self.col + 1,
+                _ => 0,
+            };
+            let line = &self.lines[row];
+            let found = (from..=line.len().saturating_sub(needle.len()))
+                .find(|&col| line.len() >= needle.len() && line[col..col + needle.len()] == needle[..]);
+            if let Some(col) = found {
+                self.row = row;
+                self.col = col;
+                return;
+            }
+        }
+        self.message = format!("Not found: {}", self.last_search);
+    }
+
+    fn move_rows(&mut self, delta: isize) {
+        let last = self.lines.len() as isize - 1;
+        self.row = (self.row as isize + delta).clamp(0, last) as usize;
+        self.col = self.col.min(self.lines[self.row].len());
+    }
+
+    fn insert(&mut self, character: char) {
+        self.lines[self.row].insert(self.col, Symbol::Char(character));
+        self.col += 1;
+        self.modified = true;
+    }
+
+    fn split_line(&mut self) {
+        let rest = self.lines[self.row].split_off(self.col);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.col = 0;
+        self.modified = true;
+    }
+
+    fn delete_before_cursor(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            self.lines[self.row].remove(self.col);
+        } else if self.row > 0 {
+            // Join the line onto the one above
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.lines[self.row].len();
+            self.lines[self.row].extend(line);
+        } else {
+            return;
+        }
+        self.modified = true;
+    }
+
+    fn delete_at_cursor(&mut self) {
+        if self.col < self.lines[self.row].len() {
+            self.lines[self.row].remove(self.col);
+        } else if self.row + 1 < self.lines.len() {
+            let next = self.lines.remove(self.row + 1);
+            self.lines[self.row].extend(next);
+        } else {
+            return;
+        }
+        self.modified = true;
+    }
+
+    async fn save(&mut self) {
+        let data = encode_lines(&self.lines);
+        self.message = match write_or_create(self.path, &data).await {
+            Ok(()) => {
+                self.modified = false;
+                format!("Wrote {} bytes", data.len())
+            }
+            Err(e) => format!("Save failed: {}", e),
+        };
+    }
+
+    /// Redraws the visible text and the status line, keeping the cursor on screen.
+    fn render(&mut self) {
+        if self.row < self.top {
+            self.top = self.row;
+        } else if self.row >= self.top + TEXT_ROWS {
+            self.top = self.row + 1 - TEXT_ROWS;
+        }
+        let cursor = screen_width(&self.lines[self.row][..self.col]);
+        if cursor < self.left {
+            self.left = cursor;
+        } else if cursor >= self.left + BUFFER_WIDTH {
+            self.left = cursor + 1 - BUFFER_WIDTH;
+        }
+        let status = self.status_line();
+
+        interrupts::without_interrupts(|| {
+            let mut writer = WRITER.lock();
+            for screen_row in 0..TEXT_ROWS {
+                let line = self.lines.get(self.top + screen_row);
+                let cells = line.map(|line| screen_cells(line)).unwrap_or_default();
+                for screen_col in 0..BUFFER_WIDTH {
+                    let byte = match (line, cells.get(self.left + screen_col)) {
+                        (None, _) if screen_col == 0 => b'~', // past the end of the file
+                        (_, Some(&byte)) => byte,
+                        _ => b' ',
+                    };
+                    // The cursor is drawn by inverting the cell under it
+                    let (foreground, background) =
+                        if self.top + screen_row == self.row && self.left + screen_col == cursor {
+                            (Color::Black, Color::LightGray)
+                        } else {
+                            (Color::LightGray, Color::Black)
+                        };
+                    writer.write_at(screen_row, screen_col, byte, foreground, background);
+                }
+            }
+            let mut status = status.chars().map(cp437::encode);
+            for col in 0..BUFFER_WIDTH {
+                let byte = status.next().unwrap_or(b' ');
+                writer.write_at(TEXT_ROWS, col, byte, Color::Black, Color::Cyan);
+            }
+        });
+    }
+
+    fn status_line(&self) -> String {
+        if let Mode::Search(query) = &self.mode {
+            return format!("Search: {}_   (Enter: find, Esc: cancel)", query);
+        }
+        if !self.message.is_empty() {
+            return self.message.clone();
+        }
+        format!(
+            " {}{}   Ln {}/{}, Col {}   ^S save  ^Q quit  ^F find",
+            self.path,
+            if self.modified { " [+]" } else { "" },
+            self.row + 1,
+            self.lines.len(),
+            self.col + 1,
+        )
+    }
+}
+
+/// Decodes one line of the file, keeping the bytes that are not valid UTF-8 as raw bytes.
+fn decode_line(bytes: &[u8]) -> Vec<Symbol> {
+    let mut line = Vec::new();
+    for chunk in bytes.utf8_chunks() {
+        line.extend(chunk.valid().chars().map(Symbol::Char));
+        line.extend(chunk.invalid().iter().map(|&byte| Symbol::Raw(byte)));
+    }
+    line
+}
+
+/// Joins `lines` back into the bytes of the file, the inverse of `decode_line`.
+fn encode_lines(lines: &[Vec<Symbol>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            data.push(b'\n');
+        }
+        for &symbol in line {
+            match symbol {
+                Symbol::Char(character) => data.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes()),
+                Symbol::Raw(byte) => data.push(byte),
+            }
+        }
+    }
+    data
+}
+
+/// The screen cells `line` takes up, with each tab widened to the next tab stop.
+fn screen_cells(line: &[Symbol]) -> Vec<u8> {
+    let mut cells = Vec::new();
+    for &symbol in line {
+        match symbol {
+            Symbol::Char('\t') => cells.resize((cells.len() / TAB_WIDTH + 1) * TAB_WIDTH, b' '),
+            Symbol::Char(character) => cells.push(cp437::encode(character)),
+            Symbol::Raw(byte) => cells.push(byte),
+        }
+    }
+    cells
+}
+
+/// The number of screen columns `line` takes up.
+fn screen_width(line: &[Symbol]) -> usize {
+    line.iter().fold(0, |width, &symbol| match symbol {
+        Symbol::Char('\t') => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+        _ => width + 1,
+    })
+}
+
+struct Edit;
+
+impl Command for Edit {
+    fn name(&self) -> &'static str { "edit" }
+    fn usage(&self) -> &'static str { "edit <file>" }
+    fn help(&self) -> &'static str { "Edit a file full-screen: Ctrl+S saves, Ctrl+Q quits, Ctrl+F searches" }
+    fn args(&self) -> Args { Args::Exactly(1) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let mut editor = Editor::open(args[0]).await?;
+        // The shell's screen comes back once the editor closes
+        // The editor draws its own cursor, so the hardware one is hidden meanwhile
+        let (saved, cursor_visible) = interrupts::without_interrupts(|| {
+            let mut writer = WRITER.lock();
+            let cursor_visible = writer.cursor_visible();
+            writer.set_cursor_visible(false);
+            (writer.save_screen(), cursor_visible)
+        });
+        editor.run().await;
+        clear_interrupt(); // Ctrl+C is an ordinary key inside the editor
+        interrupts::without_interrupts(|| {
+            let mut writer = WRITER.lock();
+            writer.restore_screen(&saved);
+            writer.set_cursor_visible(cursor_visible);
+        });
+        Ok(())
+    }
+}
+
+#[test_case]
+fn test_lines_keep_the_file_bytes() {
+    let data = b"a\tb\n\xff\xc3\xa9\xef\x9f\xbf\r\n"; // U+F7FF is kept as a character
+    let lines: Vec<Vec<Symbol>> = data.split(|&byte| byte == b'\n').map(decode_line).collect();
+    assert_eq!(lines.len(), 3); // the final newline leaves an empty last line
+    assert_eq!(encode_lines(&lines), data);
+    assert_eq!(screen_cells(&decode_line(b"a\tb")), b"a   b");
+    assert_eq!(screen_width(&decode_line(b"\tab\t")), 2 * TAB_WIDTH);
+}
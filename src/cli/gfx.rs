@@ -0,0 +1,118 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use omega::allocator::{heap_free, HEAP_SIZE};
+use omega::framebuffer::{self, console::Console, font::Font, Rgb};
+use omega::keyboard::{clear_interrupt, keymap_name, read_key};
+use omega::mouse;
+use omega::vga_buffer::Color;
+use crate::cli::Shell;
+use crate::cli::command::{Args, Command, CommandError};
+use crate::cli::io::Io;
+use crate::cli::registry::register;
+
+pub fn register_commands() {
+    register(&Gfx);
+}
+
+// Resolution used when none is given
+const DEFAULT_MODE: (usize, usize) = (1024, 768);
+// Text rows taken by the title bar, and the first row of the status fields below it
+const TITLE_ROWS: usize = 2;
+const FIRST_ROW: usize = TITLE_ROWS + 1;
+// Columns of the field names, and of the values and gauges after them
+const NAME_COLUMN: usize = 2;
+const VALUE_COLUMN: usize = NAME_COLUMN + 10;
+const GAUGE_COLUMNS: usize = 32;
+
+const PALETTE: [Color; 16] = [
+    Color::Black, Color::Blue, Color::Green, Color::Cyan,
+    Color::Red, Color::Magenta, Color::Brown, Color::LightGray,
+    Color::DarkGray, Color::LightBlue, Color::LightGreen, Color::LightCyan,
+    Color::LightRed, Color::Pink, Color::Yellow, Color::White,
+];
+
+struct Gfx;
+
+impl Command for Gfx {
+    fn name(&self) -> &'static str { "gfx" }
+    fn usage(&self) -> &'static str { "gfx [WIDTHxHEIGHT]" }
+    fn help(&self) -> &'static str { "Show the system status in a graphics mode, 1024x768 by default, until a key is pressed" }
+    fn args(&self) -> Args { Args::Range(0, 1) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let (width, height) = match args.first() {
+            Some(mode) => parse_mode(mode).ok_or(CommandError::Usage)?,
+            None => DEFAULT_MODE,
+        };
+        let framebuffer = framebuffer::enter(width, height)
+            .map_err(|e| CommandError::Other(format!("Cannot enter graphics mode: {}", e)))?;
+        let mut console = Console::new(framebuffer, Font::default());
+        let drawn = draw_dashboard(&mut console);
+        read_key().await;
+        clear_interrupt(); // Ctrl+C only closes the dashboard
+        framebuffer::leave(console.into_framebuffer());
+        drawn.map_err(CommandError::from)
+    }
+}
+
+/// Parses a resolution like `800x600`.
+fn parse_mode(mode: &str) -> Option<(usize, usize)> {
+    let (width, height) = mode.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+fn draw_dashboard(console: &mut Console) -> fmt::Result {
+    let font = Font::default();
+    let (cell_width, cell_height) = (font.width(), font.height());
+    let (width, height) = (console.framebuffer().width(), console.framebuffer().height());
+
+    let title = Rgb::from(Color::Blue);
+    console.framebuffer().fill_rect(0, 0, width, TITLE_ROWS * cell_height, title);
+    console.framebuffer().draw_text(NAME_COLUMN * cell_width, cell_height / 2, &font, "OmegaOS status", Rgb::WHITE, title);
+
+    let used = HEAP_SIZE as usize - heap_free().min(HEAP_SIZE as usize);
+    let mouse = match (mouse::is_present(), mouse::packet_size()) {
+        (false, _) => "none",
+        (true, 4) => "PS/2 with wheel",
+        (true, _) => "PS/2",
+    };
+    let fields = [
+        ("Display", format!("{}x{}, {} by {} characters", width, height, console.columns(), console.rows())),
+        ("Keyboard", keymap_name()),
+        ("Mouse", String::from(mouse)),
+        ("Heap", format!("{} of {} KiB in use", used.div_ceil(1024), HEAP_SIZE / 1024)),
+    ];
+    for (index, (name, value)) in fields.iter().enumerate() {
+        console.set_position(FIRST_ROW + index, NAME_COLUMN);
+        write!(console, "\x1b[1;37m{}\x1b[0m", name)?;
+        console.set_position(FIRST_ROW + index, VALUE_COLUMN);
+        write!(console, "{}", value)?;
+    }
+
+    // Heap gauge under its field, red once three quarters are in use
+    let gauge_row = FIRST_ROW + fields.len();
+    let (x, y) = (VALUE_COLUMN * cell_width, gauge_row * cell_height + 2);
+    let (gauge_width, gauge_height) = (GAUGE_COLUMNS * cell_width, cell_height - 4);
+    let filled = (gauge_width - 2) * used / HEAP_SIZE as usize;
+    let color = if used * 4 < HEAP_SIZE as usize * 3 { Color::LightGreen } else { Color::LightRed };
+    console.framebuffer().rect(x, y, gauge_width, gauge_height, Rgb::WHITE);
+    console.framebuffer().fill_rect(x + 1, y + 1, filled, gauge_height - 2, color.into());
+
+    let separator = (gauge_row + 1) * cell_height + cell_height / 2;
+    console.framebuffer().line(cell_width, separator, width.saturating_sub(cell_width), separator, Color::DarkGray.into());
+
+    // The text mode palette, one swatch per color
+    let palette_row = gauge_row + 2;
+    console.set_position(palette_row, NAME_COLUMN);
+    write!(console, "\x1b[1;37mColors\x1b[0m")?;
+    let swatch = 2 * cell_width;
+    let image: Vec<Rgb> = (0..cell_height - 4)
+        .flat_map(|_| PALETTE.iter().flat_map(|&color| core::iter::repeat_n(Rgb::from(color), swatch)))
+        .collect();
+    console.framebuffer().blit(VALUE_COLUMN * cell_width, palette_row * cell_height + 2, PALETTE.len() * swatch, &image);
+
+    console.set_position(console.rows() - 1, NAME_COLUMN);
+    write!(console, "\x1b[36mPress any key to return to the shell\x1b[0m")
+}
@@ -0,0 +1,87 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use omega::keyboard::{interrupted, read_input};
+use omega::print;
+
+/// Where a command reads its input from.
+pub enum Stdin {
+    Console,       // lines typed on the keyboard
+    Data(Vec<u8>), // a redirected file or the output of the previous pipeline stage
+}
+
+impl Stdin {
+    pub fn is_console(&self) -> bool {
+        matches!(self, Stdin::Console)
+    }
+
+    /// Reads all remaining input. On the console that is every line typed until
+    /// Ctrl+D on an empty line, or until Ctrl+C.
+    pub async fn read_to_end(&mut self) -> Vec<u8> {
+        match self {
+            Stdin::Console => {
+                let mut data = Vec::new();
+                while let Some(line) = read_input().await {
+                    data.extend_from_slice(line.as_bytes());
+                    data.push(b'\n');
+                }
+                if interrupted() {
+                    data.clear();
+                }
+                data
+            }
+            Stdin::Data(data) => core::mem::take(data),
+        }
+    }
+
+    /// Takes the input for a command, leaving none behind unless it is the console.
+    pub fn take(&mut self) -> Stdin {
+        match self {
+            Stdin::Console => Stdin::Console,
+            Stdin::Data(data) => Stdin::Data(core::mem::take(data)),
+        }
+    }
+}
+
+/// Where a command writes its output to.
+pub enum Stdout {
+    Console,       // the VGA console
+    Pipe(Vec<u8>), // an in-memory pipe, drained into a file or the next pipeline stage
+}
+
+impl Stdout {
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        match self {
+            Stdout::Console => print!("{}", String::from_utf8_lossy(data)),
+            Stdout::Pipe(buffer) => buffer.extend_from_slice(data),
+        }
+    }
+
+    /// Takes whatever was written into the pipe so far.
+    pub fn take(&mut self) -> Vec<u8> {
+        match self {
+            Stdout::Console => Vec::new(),
+            Stdout::Pipe(buffer) => core::mem::take(buffer),
+        }
+    }
+}
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// The streams a command runs with.
+pub struct Io {
+    pub stdin: Stdin,
+    pub stdout: Stdout,
+}
+
+impl Io {
+    /// The streams of the interactive shell.
+    pub fn console() -> Self {
+        Io { stdin: Stdin::Console, stdout: Stdout::Console }
+    }
+}
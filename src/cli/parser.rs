@@ -0,0 +1,348 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::Peekable;
+use core::str::Chars;
+use crate::cli::vars::is_valid_name;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Pipe,        // |
+    RedirectOut, // >
+    Append,      // >>
+    RedirectIn,  // <
+}
+
+/// Where a stage sends its output, if not to the next stage or the console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub path: String,
+    pub append: bool,
+}
+
+/// One command of a pipeline with its redirections.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Stage {
+    pub args: Vec<String>, // the command name followed by its arguments
+    pub input: Option<String>,
+    pub output: Option<Redirect>,
+}
+
+/// How a pipeline of a command list depends on the status of the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    Always, // first pipeline or after `;`
+    And,    // after `&&`: runs only if the previous status is 0
+    Or,     // after `||`: runs only if the previous status is not 0
+}
+
+/// Errors in the syntax of a command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    EmptyStage,                // a `|` or redirection with no command
+    MissingPath(&'static str), // a redirection operator not followed by a file name
+    UnterminatedQuote(char),   // a quote with no matching closing quote
+    TrailingBackslash,         // a `\` at the very end of the line
+    BadSubstitution,           // a `${` with no matching `}` or an invalid name inside
+    UnexpectedOperator,        // an operator where only words are allowed
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::EmptyStage => f.write_str("Syntax error: empty command in pipeline"),
+            ParseError::MissingPath(operator) => write!(f, "Syntax error: missing file name after '{}'", operator),
+            ParseError::UnterminatedQuote(quote) => write!(f, "Syntax error: unterminated {} quote", quote),
+            ParseError::TrailingBackslash => f.write_str("Syntax error: backslash at end of line"),
+            ParseError::BadSubstitution => f.write_str("Syntax error: bad substitution"),
+            ParseError::UnexpectedOperator => f.write_str("Syntax error: unexpected operator"),
+        }
+    }
+}
+
+/// Splits a line into words and the operators `|`, `>`, `>>` and `<`.
+///
+/// Inside single quotes every character is literal. Inside double quotes only
+/// `$` expansions and the escapes `\"`, `\\` and `\$` are recognized. Outside
+/// quotes a backslash makes the next character literal. `$NAME`, `${NAME}` and
+/// `$?` are replaced by `lookup`, an unknown name expanding to nothing; the
+/// result is never split into several words. A `#` starting a word begins a
+/// comment.
+fn tokenize(line: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quoted = false; // a word with quotes is kept even if empty, so "" is an empty argument
+    let mut chars = line.chars().peekable();
+    while let Some(character) = chars.next() {
+        let operator = match character {
+            '|' => Some(Token::Pipe),
+            '<' => Some(Token::RedirectIn),
+            '>' if chars.peek() == Some(&'>') => {
+                chars.next();
+                Some(Token::Append)
+            }
+            '>' => Some(Token::RedirectOut),
+            _ => None,
+        };
+        if operator.is_some() || character.is_whitespace() {
+            if in_word {
+                // An unquoted word that expanded to nothing, like `$UNSET`, is dropped
+                if quoted || !word.is_empty() {
+                    tokens.push(Token::Word(core::mem::take(&mut word)));
+                }
+                in_word = false;
+                quoted = false;
+            }
+            tokens.extend(operator);
+            continue;
+        }
+        if character == '#' && !in_word {
+            break; // a comment runs to the end of the line
+        }
+        in_word = true;
+        quoted |= matches!(character, '\'' | '"');
+        match character {
+            '\'' => loop {
+                match chars.next() {
+                    Some('\'') => break,
+                    Some(quoted) => word.push(quoted),
+                    None => return Err(ParseError::UnterminatedQuote('\'')),
+                }
+            },
+            '"' => loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') if matches!(chars.peek(), Some('"' | '\\' | '$')) => word.extend(chars.next()),
+                    Some('$') => expand(&mut chars, &mut word, lookup)?,
+                    Some(quoted) => word.push(quoted),
+                    None => return Err(ParseError::UnterminatedQuote('"')),
+                }
+            },
+            '\\' => word.push(chars.next().ok_or(ParseError::TrailingBackslash)?),
+            '$' => expand(&mut chars, &mut word, lookup)?,
+            _ => word.push(character),
+        }
+    }
+    if in_word && (quoted || !word.is_empty()) {
+        tokens.push(Token::Word(word));
+    }
+    Ok(tokens)
+}
+
+/// Expands the variable reference following a `$` into `word`.
+fn expand(
+    chars: &mut Peekable<Chars>,
+    word: &mut String,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<(), ParseError> {
+    let mut name = String::new();
+    match chars.peek() {
+        // Special parameters and the positional parameters `$0` to `$9` are a single character
+        Some(&character @ ('?' | '#' | '0'..='9')) => {
+            chars.next();
+            name.push(character);
+        }
+        Some('{') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(character) => name.push(character),
+                    None => return Err(ParseError::BadSubstitution),
+                }
+            }
+            let special = name.len() == 1 && name.chars().all(|c| c == '?' || c == '#' || c.is_ascii_digit());
+            if !special && !is_valid_name(&name) {
+                return Err(ParseError::BadSubstitution);
+            }
+        }
+        _ => {
+            while let Some(&character) = chars.peek() {
+                if !(character.is_ascii_alphanumeric() || character == '_') {
+                    break;
+                }
+                name.push(character);
+                chars.next();
+            }
+            if name.is_empty() {
+                word.push('$'); // a `$` not followed by a name stays literal
+                return Ok(());
+            }
+        }
+    }
+    word.push_str(&lookup(&name).unwrap_or_default());
+    Ok(())
+}
+
+/// Splits a command line at the `&&`, `||` and `;` outside quotes into its pipelines.
+///
+/// The pipelines are returned unparsed so that each one is expanded only when it
+/// runs, which lets `$?` see the status of the pipeline before it.
+pub fn split_list(line: &str) -> Result<Vec<(Connector, &str)>, ParseError> {
+    let mut list = Vec::new();
+    let mut connector = Connector::Always;
+    let mut start = 0;
+    let mut quote = None;
+    let mut word_start = true; // whether a `#` here would begin a comment
+    let mut chars = line.char_indices().peekable();
+    let mut end = line.len();
+    while let Some((index, character)) = chars.next() {
+        let at_word_start = core::mem::replace(&mut word_start, character.is_whitespace());
+        let next = match (quote, character) {
+            (Some(open), _) if character == open => {
+                quote = None;
+                None
+            }
+            (Some('"'), '\\') | (None, '\\') => {
+                chars.next(); // the escaped character cannot end anything
+                None
+            }
+            (Some(_), _) => None,
+            (None, '\'' | '"') => {
+                quote = Some(character);
+                None
+            }
+            (None, '#') if at_word_start => {
+                end = index;
+                break;
+            }
+            (None, ';') => Some((Connector::Always, 1)),
+            (None, '&') if chars.peek().map(|&(_, c)| c) == Some('&') => Some((Connector::And, 2)),
+            (None, '|') if chars.peek().map(|&(_, c)| c) == Some('|') => Some((Connector::Or, 2)),
+            _ => None,
+        };
+        if let Some((next_connector, length)) = next {
+            let pipeline = line[start..index].trim();
+            // Only `;` may follow an empty command, and only at the very start
+            if pipeline.is_empty() && (next_connector != Connector::Always || connector != Connector::Always) {
+                return Err(ParseError::EmptyStage);
+            }
+            if !pipeline.is_empty() {
+                list.push((connector, pipeline));
+            }
+            if length == 2 {
+                chars.next();
+            }
+            connector = next_connector;
+            start = index + length;
+            word_start = true;
+        }
+    }
+    if let Some(open) = quote {
+        return Err(ParseError::UnterminatedQuote(open));
+    }
+    let pipeline = line[start..end].trim();
+    if pipeline.is_empty() {
+        if connector != Connector::Always {
+            return Err(ParseError::EmptyStage); // a trailing `&&` or `||`
+        }
+    } else {
+        list.push((connector, pipeline));
+    }
+    Ok(list)
+}
+
+/// Expands `line` into plain words, rejecting operators. Used for the word list of `for`.
+pub fn parse_words(line: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<Vec<String>, ParseError> {
+    let mut stages = parse(line, lookup)?;
+    match stages.pop() {
+        None => Ok(Vec::new()),
+        Some(stage) if stages.is_empty() && stage.input.is_none() && stage.output.is_none() => Ok(stage.args),
+        Some(_) => Err(ParseError::UnexpectedOperator),
+    }
+}
+
+/// Parses a command line into the stages of a pipeline, expanding variables through `lookup`.
+/// A blank line gives no stages.
+pub fn parse(line: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<Vec<Stage>, ParseError> {
+    let mut stages = Vec::new();
+    let mut stage = Stage::default();
+    let mut tokens = tokenize(line, lookup)?.into_iter();
+    while let Some(token) = tokens.next() {
+        let operator = match token {
+            Token::Word(word) => {
+                stage.args.push(word);
+                continue;
+            }
+            Token::Pipe => {
+                if stage.args.is_empty() {
+                    return Err(ParseError::EmptyStage);
+                }
+                stages.push(core::mem::take(&mut stage));
+                continue;
+            }
+            Token::RedirectOut => ">",
+            Token::Append => ">>",
+            Token::RedirectIn => "<",
+        };
+        let Some(Token::Word(path)) = tokens.next() else {
+            return Err(ParseError::MissingPath(operator));
+        };
+        match operator {
+            "<" => stage.input = Some(path),
+            _ => stage.output = Some(Redirect { path, append: operator == ">>" }),
+        }
+    }
+    if stage.args.is_empty() {
+        // Only a completely blank line may end without a command
+        if !stages.is_empty() || stage.input.is_some() || stage.output.is_some() {
+            return Err(ParseError::EmptyStage);
+        }
+        return Ok(stages);
+    }
+    stages.push(stage);
+    Ok(stages)
+}
+
+#[test_case]
+fn test_parse_pipeline_with_redirections() {
+    let stages = parse("cat<in.txt|grep error>>log", &|_| None).unwrap();
+    assert_eq!(stages.len(), 2);
+    assert_eq!(stages[0].args, ["cat"]);
+    assert_eq!(stages[0].input.as_deref(), Some("in.txt"));
+    assert_eq!(stages[1].args, ["grep", "error"]);
+    assert_eq!(stages[1].output, Some(Redirect { path: String::from("log"), append: true }));
+    assert_eq!(parse("ls |", &|_| None), Err(ParseError::EmptyStage));
+    assert_eq!(parse("ls >", &|_| None), Err(ParseError::MissingPath(">")));
+}
+
+#[test_case]
+fn test_parse_quotes_and_variables() {
+    let lookup = |name: &str| match name {
+        "NAME" => Some(String::from("a  b")),
+        "?" => Some(String::from("0")),
+        _ => None,
+    };
+    let stages = parse(r#"echo "x  $NAME" '$NAME|' a\ b ${NAME}$? $MISSING"" \$ a#b # comment"#, &lookup).unwrap();
+    assert_eq!(stages.len(), 1);
+    assert_eq!(stages[0].args, ["echo", "x  a  b", "$NAME|", "a b", "a  b0", "", "$", "a#b"]);
+    assert_eq!(parse("echo 'open", &lookup), Err(ParseError::UnterminatedQuote('\'')));
+    assert_eq!(parse("echo ${1x}", &lookup), Err(ParseError::BadSubstitution));
+}
+
+#[test_case]
+fn test_parse_drops_empty_unquoted_expansions() {
+    let lookup = |name: &str| match name {
+        "EMPTY" => Some(String::new()),
+        _ => None,
+    };
+    let stages = parse(r#"echo $UNSET a $EMPTY${UNSET} "$UNSET" '' b$UNSET"#, &lookup).unwrap();
+    assert_eq!(stages[0].args, ["echo", "a", "", "", "b"]);
+    assert_eq!(parse("$UNSET", &lookup), Ok(Vec::new()));
+    assert_eq!(parse("echo > $UNSET", &lookup), Err(ParseError::MissingPath(">")));
+}
+
+#[test_case]
+fn test_split_list_respects_quotes() {
+    let list = split_list("false || echo 'a && b'; ls&&cat x # c; d").unwrap();
+    assert_eq!(list, [
+        (Connector::Always, "false"),
+        (Connector::Or, "echo 'a && b'"),
+        (Connector::Always, "ls"),
+        (Connector::And, "cat x"),
+    ]);
+    assert_eq!(split_list("ls &&"), Err(ParseError::EmptyStage));
+    assert_eq!(split_list("|| ls"), Err(ParseError::EmptyStage));
+}
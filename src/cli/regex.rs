@@ -0,0 +1,140 @@
+use alloc::vec::Vec;
+
+/// A single-character pattern element.
+enum Atom {
+    Any,                                                // .
+    Char(char),
+    Class { negated: bool, ranges: Vec<(char, char)> }, // [a-z_] or [^0-9]
+}
+
+impl Atom {
+    fn matches(&self, character: char) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Char(expected) => *expected == character,
+            Atom::Class { negated, ranges } => {
+                ranges.iter().any(|&(low, high)| (low..=high).contains(&character)) != *negated
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Repeat {
+    Once,
+    ZeroOrMore, // *
+    OneOrMore,  // +
+    ZeroOrOne,  // ?
+}
+
+/// A compiled simple regular expression.
+///
+/// Supports `.`, `[...]` and `[^...]` classes with ranges, the repetitions `*`,
+/// `+` and `?`, the anchors `^` and `$`, and `\` to match the next character
+/// literally. There is no grouping or alternation.
+pub struct Regex {
+    anchored_start: bool,
+    anchored_end: bool,
+    items: Vec<(Atom, Repeat)>,
+}
+
+impl Regex {
+    /// Compiles `pattern`, or returns `None` if it is malformed.
+    pub fn new(pattern: &str) -> Option<Regex> {
+        let mut chars = pattern.chars().peekable();
+        let anchored_start = chars.next_if_eq(&'^').is_some();
+        let mut anchored_end = false;
+        let mut items = Vec::new();
+        while let Some(character) = chars.next() {
+            let atom = match character {
+                '$' if chars.peek().is_none() => {
+                    anchored_end = true;
+                    break;
+                }
+                '.' => Atom::Any,
+                '\\' => Atom::Char(chars.next()?),
+                '[' => {
+                    let negated = chars.next_if_eq(&'^').is_some();
+                    let mut ranges = Vec::new();
+                    let mut first = true;
+                    loop {
+                        let low = chars.next()?;
+                        if low == ']' && !first {
+                            break;
+                        }
+                        first = false;
+                        let high = match chars.peek() {
+                            Some('-') => {
+                                chars.next();
+                                match chars.next()? {
+                                    ']' => {
+                                        // A trailing `-` is literal
+                                        ranges.push((low, low));
+                                        ranges.push(('-', '-'));
+                                        break;
+                                    }
+                                    high => high,
+                                }
+                            }
+                            _ => low,
+                        };
+                        ranges.push((low, high));
+                    }
+                    Atom::Class { negated, ranges }
+                }
+                '*' | '+' | '?' => return None, // nothing to repeat
+                _ => Atom::Char(character),
+            };
+            let repeat = match chars.peek() {
+                Some('*') => Repeat::ZeroOrMore,
+                Some('+') => Repeat::OneOrMore,
+                Some('?') => Repeat::ZeroOrOne,
+                _ => Repeat::Once,
+            };
+            if repeat != Repeat::Once {
+                chars.next();
+            }
+            items.push((atom, repeat));
+        }
+        Some(Regex { anchored_start, anchored_end, items })
+    }
+
+    /// Whether the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        if self.anchored_start {
+            return self.match_here(0, &text);
+        }
+        (0..=text.len()).any(|start| self.match_here(0, &text[start..]))
+    }
+
+    /// Whether the items from `item` on match at the start of `text`, backtracking over repetitions.
+    fn match_here(&self, item: usize, text: &[char]) -> bool {
+        let Some((atom, repeat)) = self.items.get(item) else {
+            return !self.anchored_end || text.is_empty();
+        };
+        let (min, max) = match repeat {
+            Repeat::Once => (1, 1),
+            Repeat::ZeroOrMore => (0, usize::MAX),
+            Repeat::OneOrMore => (1, usize::MAX),
+            Repeat::ZeroOrOne => (0, 1),
+        };
+        // Take as many characters as possible, then give them back one at a time
+        let available = text.iter().take(max).take_while(|&&character| atom.matches(character)).count();
+        (min..=available).rev().any(|taken| self.match_here(item + 1, &text[taken..]))
+    }
+}
+
+#[test_case]
+fn test_regex_matching() {
+    let matches = |pattern: &str, text: &str| Regex::new(pattern).unwrap().is_match(text);
+    assert!(matches("error", "an error here"));
+    assert!(matches("^err.r$", "error"));
+    assert!(!matches("^rror", "error"));
+    assert!(matches("a[0-9]+b", "xa123b"));
+    assert!(!matches("a[^0-9]b", "a1b"));
+    assert!(matches("colou?r", "color"));
+    assert!(matches("a\\.b", "a.b") && !matches("a\\.b", "axb"));
+    assert!(matches("x*$", ""));
+    assert!(Regex::new("*a").is_none() && Regex::new("[ab").is_none());
+}
@@ -0,0 +1,36 @@
+use alloc::vec::Vec;
+use spin::RwLock;
+use crate::cli::command::DynCommand;
+
+// Every command the shell knows, in registration order
+static REGISTRY: RwLock<Vec<&'static dyn DynCommand>> = RwLock::new(Vec::new());
+
+/// Makes `command` available to the shell. Called by modules during init.
+///
+/// Panics if the name or an alias is already taken, since that is a programming error.
+pub fn register(command: &'static dyn DynCommand) {
+    let mut registry = REGISTRY.write();
+    for name in core::iter::once(command.name()).chain(command.aliases().iter().copied()) {
+        assert!(
+            !registry.iter().any(|other| answers_to(*other, name)),
+            "command name '{}' registered twice", name
+        );
+    }
+    registry.push(command);
+}
+
+/// Looks up a command by its name or one of its aliases.
+pub fn find(name: &str) -> Option<&'static dyn DynCommand> {
+    REGISTRY.read().iter().copied().find(|command| answers_to(*command, name))
+}
+
+/// All registered commands, sorted by name.
+pub fn commands() -> Vec<&'static dyn DynCommand> {
+    let mut commands = REGISTRY.read().clone();
+    commands.sort_by_key(|command| command.name());
+    commands
+}
+
+fn answers_to(command: &dyn DynCommand, name: &str) -> bool {
+    command.name() == name || command.aliases().contains(&name)
+}
@@ -0,0 +1,236 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use omega::println;
+use crate::cli::{check_interrupt, handle_command, Shell, STATUS_USAGE};
+use crate::cli::command::{Args, Command, CommandError};
+use crate::cli::io::Io;
+use crate::cli::parser::parse_words;
+use crate::cli::registry::register;
+use crate::fs::vfs::read_file;
+
+pub fn register_commands() {
+    register(&Sh);
+    register(&Source);
+}
+
+/// A parsed script statement.
+///
+/// Conditions are ordinary command lines; a status of 0 counts as true.
+enum Node {
+    Command(String),
+    If { condition: String, then: Vec<Node>, otherwise: Vec<Node> },
+    While { condition: String, body: Vec<Node> },
+    For { name: String, words: String, body: Vec<Node> },
+}
+
+/// A syntax error in a script, with the line it was found on.
+struct ScriptError {
+    line: usize,
+    message: &'static str,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Builds the statement tree of a script, one statement per line.
+///
+/// Blocks are written as
+///
+/// ```text
+/// if <command> [; then]     while <command> [; do]     for NAME in <words> [; do]
+///     ...                       ...                        ...
+/// else                      done                       done
+///     ...
+/// fi
+/// ```
+///
+/// where `then` and `do` may also stand on a line of their own.
+struct ScriptParser<'a> {
+    lines: Vec<(usize, &'a str)>, // non-blank lines with their line numbers
+    next: usize,
+}
+
+impl<'a> ScriptParser<'a> {
+    fn new(text: &'a str) -> Self {
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        ScriptParser { lines, next: 0 }
+    }
+
+    /// Parses statements until one of `terminators` (or the end, if `terminators` is empty),
+    /// and returns them with the terminator that ended the block.
+    fn block(&mut self, terminators: &[&'static str]) -> Result<(Vec<Node>, &'static str), ScriptError> {
+        let mut nodes = Vec::new();
+        while let Some(&(number, line)) = self.lines.get(self.next) {
+            self.next += 1;
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            if let Some(&terminator) = terminators.iter().find(|&&terminator| terminator == keyword) {
+                return Ok((nodes, terminator));
+            }
+            let node = match keyword {
+                "if" => {
+                    let condition = self.header(rest, "then");
+                    let (then, end) = self.block(&["else", "fi"]).map_err(|e| self.unclosed(e, number, "'if' without 'fi'"))?;
+                    let otherwise = match end {
+                        "else" => self.block(&["fi"]).map_err(|e| self.unclosed(e, number, "'else' without 'fi'"))?.0,
+                        _ => Vec::new(),
+                    };
+                    Node::If { condition, then, otherwise }
+                }
+                "while" => {
+                    let condition = self.header(rest, "do");
+                    let body = self.block(&["done"]).map_err(|e| self.unclosed(e, number, "'while' without 'done'"))?.0;
+                    Node::While { condition, body }
+                }
+                "for" => {
+                    let header = self.header(rest, "do");
+                    let (name, words) = header.split_once(char::is_whitespace).unwrap_or((&header, ""));
+                    let words = words.trim_start().strip_prefix("in").filter(|words| words.is_empty() || words.starts_with(' '));
+                    let Some(words) = words else {
+                        return Err(ScriptError { line: number, message: "expected 'for NAME in WORDS'" });
+                    };
+                    let (name, words) = (String::from(name), String::from(words));
+                    let body = self.block(&["done"]).map_err(|e| self.unclosed(e, number, "'for' without 'done'"))?.0;
+                    Node::For { name, words, body }
+                }
+                "then" | "do" | "else" | "fi" | "done" => {
+                    return Err(ScriptError { line: number, message: "unexpected keyword" });
+                }
+                _ => Node::Command(String::from(line)),
+            };
+            nodes.push(node);
+        }
+        match terminators {
+            [] => Ok((nodes, "")),
+            _ => Err(ScriptError { line: 0, message: "" }), // reported by the caller, which knows the opening line
+        }
+    }
+
+    /// Strips `; then` or `; do` from a block header, or skips that keyword on the next line.
+    fn header(&mut self, rest: &str, keyword: &str) -> String {
+        let rest = rest.trim();
+        if let Some(condition) = rest.strip_suffix(keyword).and_then(|head| head.trim_end().strip_suffix(';')) {
+            return String::from(condition.trim_end());
+        }
+        if self.lines.get(self.next).map(|&(_, line)| line) == Some(keyword) {
+            self.next += 1;
+        }
+        String::from(rest)
+    }
+
+    /// Turns the end-of-script error of an inner block into one pointing at its opening line.
+    fn unclosed(&self, error: ScriptError, line: usize, message: &'static str) -> ScriptError {
+        if error.line == 0 { ScriptError { line, message } } else { error }
+    }
+}
+
+/// Runs the statements until they finish, `exit` is called or Ctrl+C is pressed.
+async fn run(nodes: &[Node], shell: &mut Shell, io: &mut Io) {
+    for node in nodes {
+        if shell.exit.is_some() || check_interrupt(shell) {
+            return;
+        }
+        match node {
+            Node::Command(line) => handle_command(line, shell, io).await,
+            Node::If { condition, then, otherwise } => {
+                handle_command(condition, shell, io).await;
+                if shell.exit.is_none() {
+                    Box::pin(run(if shell.status == 0 { then } else { otherwise }, shell, io)).await;
+                }
+            }
+            Node::While { condition, body } => loop {
+                handle_command(condition, shell, io).await;
+                if check_interrupt(shell) {
+                    break;
+                }
+                if shell.status != 0 || shell.exit.is_some() {
+                    shell.status = 0; // a loop that ran to completion succeeded
+                    break;
+                }
+                Box::pin(run(body, shell, io)).await;
+            },
+            Node::For { name, words, body } => {
+                let words = match parse_words(words, &|name| shell.lookup(name)) {
+                    Ok(words) => words,
+                    Err(e) => {
+                        println!("{}", e);
+                        shell.status = STATUS_USAGE;
+                        return;
+                    }
+                };
+                for word in words {
+                    shell.vars.set(name, &word);
+                    Box::pin(run(body, shell, io)).await;
+                    if shell.exit.is_some() || check_interrupt(shell) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Runs the script at `path` in `shell`, so the variables it sets stay set.
+///
+/// Its commands read from and write to `io` unless they redirect.
+/// Returns the status of the script: the one given to `exit`, or that of its last command.
+pub async fn source_file(shell: &mut Shell, io: &mut Io, path: &str) -> Result<u8, CommandError> {
+    let data = read_file(path).await?;
+    let text = String::from_utf8_lossy(&data);
+    let nodes = ScriptParser::new(&text)
+        .block(&[])
+        .map_err(|e| CommandError::Other(format!("{}: {}", path, e)))?
+        .0;
+    run(&nodes, shell, io).await;
+    Ok(shell.exit.unwrap_or(shell.status))
+}
+
+fn status_result(status: u8) -> Result<(), CommandError> {
+    if status == 0 { Ok(()) } else { Err(CommandError::Status(status)) }
+}
+
+struct Sh;
+
+impl Command for Sh {
+    fn name(&self) -> &'static str { "sh" }
+    fn usage(&self) -> &'static str { "sh <file> [args...]" }
+    fn help(&self) -> &'static str { "Run a script in a new shell that sees only exported variables; $1..$9 are its arguments" }
+    fn args(&self) -> Args { Args::AtLeast(1) }
+
+    async fn run(&self, shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let mut child = Shell::new();
+        for (name, value) in shell.vars.iter(true) {
+            child.vars.set(name, value);
+            child.vars.export(name);
+        }
+        for (index, arg) in args.iter().enumerate().take(10) {
+            child.vars.set(&format!("{}", index), arg);
+        }
+        child.vars.set("#", &format!("{}", args.len() - 1));
+        status_result(source_file(&mut child, io, args[0]).await?)
+    }
+}
+
+struct Source;
+
+impl Command for Source {
+    fn name(&self) -> &'static str { "source" }
+    fn aliases(&self) -> &'static [&'static str] { &["."] }
+    fn usage(&self) -> &'static str { "source <file>" }
+    fn help(&self) -> &'static str { "Run a script in the current shell, keeping the variables it sets" }
+    fn args(&self) -> Args { Args::Exactly(1) }
+
+    async fn run(&self, shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        status_result(source_file(shell, io, args[0]).await?)
+    }
+}
@@ -0,0 +1,287 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use crate::cli::Shell;
+use crate::cli::command::{parse_options, Args, Command, CommandError};
+use crate::cli::io::Io;
+use crate::cli::regex::Regex;
+use crate::cli::registry::register;
+use crate::fs::vfs::read_file;
+
+/// Registers the commands that filter text and inspect binary data.
+pub fn register_commands() {
+    register(&Grep);
+    register(&Wc);
+    register(&Head);
+    register(&Tail);
+    register(&Sort);
+    register(&Uniq);
+    register(&Hexdump);
+    register(&Xxd);
+}
+
+/// Reads the named files one after the other, or the command's input if there are none.
+async fn read_input(io: &mut Io, files: &[&str]) -> Result<Vec<u8>, CommandError> {
+    if files.is_empty() {
+        return Ok(io.stdin.read_to_end().await);
+    }
+    let mut data = Vec::new();
+    for file in files {
+        data.extend(read_file(file).await.map_err(|e| CommandError::Other(format!("{}: {}", file, e)))?);
+    }
+    Ok(data)
+}
+
+/// Reads the input like `read_input` and splits it into lines.
+async fn read_lines(io: &mut Io, files: &[&str]) -> Result<Vec<String>, CommandError> {
+    let data = read_input(io, files).await?;
+    Ok(String::from_utf8_lossy(&data).lines().map(String::from).collect())
+}
+
+struct Grep;
+
+impl Command for Grep {
+    fn name(&self) -> &'static str { "grep" }
+    fn usage(&self) -> &'static str { "grep [-Fivnc] <pattern> [file...]" }
+    fn help(&self) -> &'static str {
+        "Print lines matching a simple regex (-F: plain text, -i: ignore case, -v: non-matching, -n: numbers, -c: count)"
+    }
+    fn args(&self) -> Args { Args::AtLeast(1) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "Fivnc", "")?;
+        let (&pattern, files) = options.operands.split_first().ok_or(CommandError::Usage)?;
+        let ignore_case = options.has('i');
+        let pattern = if ignore_case { pattern.to_lowercase() } else { String::from(pattern) };
+        let regex = match options.has('F') {
+            true => None,
+            false => Some(Regex::new(&pattern).ok_or_else(|| CommandError::Other(format!("Invalid pattern: {}", pattern)))?),
+        };
+
+        let mut count = 0;
+        for (number, line) in read_lines(io, files).await?.iter().enumerate() {
+            let folded = if ignore_case { line.to_lowercase() } else { line.clone() };
+            let found = match &regex {
+                Some(regex) => regex.is_match(&folded),
+                None => folded.contains(pattern.as_str()),
+            };
+            if found == options.has('v') {
+                continue;
+            }
+            count += 1;
+            if options.has('c') {
+                continue;
+            }
+            if options.has('n') {
+                write!(io.stdout, "{}:", number + 1)?;
+            }
+            writeln!(io.stdout, "{}", line)?;
+        }
+        if options.has('c') {
+            writeln!(io.stdout, "{}", count)?;
+        }
+        // Like Unix grep, finding nothing is a failure so scripts can test for it
+        if count == 0 { Err(CommandError::Status(1)) } else { Ok(()) }
+    }
+}
+
+struct Wc;
+
+impl Command for Wc {
+    fn name(&self) -> &'static str { "wc" }
+    fn usage(&self) -> &'static str { "wc [-lwc] [file...]" }
+    fn help(&self) -> &'static str { "Count lines, words and bytes" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "lwc", "")?;
+        let data = read_input(io, &options.operands).await?;
+        let text = String::from_utf8_lossy(&data);
+        let counts = [
+            ('l', data.iter().filter(|&&byte| byte == b'\n').count()),
+            ('w', text.split_whitespace().count()),
+            ('c', data.len()),
+        ];
+        // Without options all three counts are shown
+        let all = !counts.iter().any(|&(option, _)| options.has(option));
+        for (option, count) in counts {
+            if all || options.has(option) {
+                write!(io.stdout, "{:>8}", count)?;
+            }
+        }
+        writeln!(io.stdout)?;
+        Ok(())
+    }
+}
+
+struct Head;
+
+impl Command for Head {
+    fn name(&self) -> &'static str { "head" }
+    fn usage(&self) -> &'static str { "head [-n lines] [file...]" }
+    fn help(&self) -> &'static str { "Print the first lines, 10 by default" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "", "n")?;
+        let count = options.number('n', 10)?;
+        for line in read_lines(io, &options.operands).await?.iter().take(count) {
+            writeln!(io.stdout, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+struct Tail;
+
+impl Command for Tail {
+    fn name(&self) -> &'static str { "tail" }
+    fn usage(&self) -> &'static str { "tail [-n lines] [file...]" }
+    fn help(&self) -> &'static str { "Print the last lines, 10 by default" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "", "n")?;
+        let count = options.number('n', 10)?;
+        let lines = read_lines(io, &options.operands).await?;
+        for line in &lines[lines.len().saturating_sub(count)..] {
+            writeln!(io.stdout, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+struct Sort;
+
+impl Command for Sort {
+    fn name(&self) -> &'static str { "sort" }
+    fn usage(&self) -> &'static str { "sort [-rnu] [file...]" }
+    fn help(&self) -> &'static str { "Sort lines (-r: reverse, -n: by leading number, -u: drop duplicates)" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "rnu", "")?;
+        let mut lines = read_lines(io, &options.operands).await?;
+        if options.has('n') {
+            // Lines without a leading number sort first, as if they were 0
+            let key = |line: &String| -> i64 {
+                let line = line.trim_start();
+                let end = line.char_indices().find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-'))).map_or(line.len(), |(i, _)| i);
+                line[..end].parse().unwrap_or(0)
+            };
+            lines.sort_by(|a, b| key(a).cmp(&key(b)).then_with(|| a.cmp(b)));
+        } else {
+            lines.sort();
+        }
+        if options.has('u') {
+            lines.dedup();
+        }
+        if options.has('r') {
+            lines.reverse();
+        }
+        for line in lines {
+            writeln!(io.stdout, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+struct Uniq;
+
+impl Command for Uniq {
+    fn name(&self) -> &'static str { "uniq" }
+    fn usage(&self) -> &'static str { "uniq [-cd] [file...]" }
+    fn help(&self) -> &'static str { "Collapse runs of identical lines (-c: prefix counts, -d: only repeated lines)" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "cd", "")?;
+        let mut runs: Vec<(usize, String)> = Vec::new();
+        for line in read_lines(io, &options.operands).await? {
+            match runs.last_mut() {
+                Some((count, previous)) if *previous == line => *count += 1,
+                _ => runs.push((1, line)),
+            }
+        }
+        for (count, line) in runs {
+            if options.has('d') && count < 2 {
+                continue;
+            }
+            if options.has('c') {
+                write!(io.stdout, "{:>7} ", count)?;
+            }
+            writeln!(io.stdout, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows a byte in the text column of a dump: printable ASCII as is, anything else as `.`.
+fn printable(byte: u8) -> char {
+    if (0x20..0x7f).contains(&byte) { byte as char } else { '.' }
+}
+
+struct Hexdump;
+
+impl Command for Hexdump {
+    fn name(&self) -> &'static str { "hexdump" }
+    fn usage(&self) -> &'static str { "hexdump [-n bytes] [file...]" }
+    fn help(&self) -> &'static str { "Dump bytes in hex with an ASCII column, like hexdump -C" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "C", "n")?; // -C is accepted since it is the only format
+        let mut data = read_input(io, &options.operands).await?;
+        data.truncate(options.number('n', data.len())?);
+        for (index, chunk) in data.chunks(16).enumerate() {
+            let mut line = format!("{:08x} ", index * 16);
+            for column in 0..16 {
+                if column == 8 {
+                    line.push(' ');
+                }
+                match chunk.get(column) {
+                    Some(byte) => write!(line, " {:02x}", byte)?,
+                    None => line.push_str("   "),
+                }
+            }
+            line.push_str("  |");
+            line.extend(chunk.iter().map(|&byte| printable(byte)));
+            line.push('|');
+            writeln!(io.stdout, "{}", line)?;
+        }
+        writeln!(io.stdout, "{:08x}", data.len())?;
+        Ok(())
+    }
+}
+
+struct Xxd;
+
+impl Command for Xxd {
+    fn name(&self) -> &'static str { "xxd" }
+    fn usage(&self) -> &'static str { "xxd [-l bytes] [file...]" }
+    fn help(&self) -> &'static str { "Dump bytes in hex, two bytes per group, with an ASCII column" }
+    fn args(&self) -> Args { Args::AtLeast(0) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let options = parse_options(args, "", "l")?;
+        let mut data = read_input(io, &options.operands).await?;
+        data.truncate(options.number('l', data.len())?);
+        for (index, chunk) in data.chunks(16).enumerate() {
+            let mut line = format!("{:08x}:", index * 16);
+            for column in 0..16 {
+                if column % 2 == 0 {
+                    line.push(' ');
+                }
+                match chunk.get(column) {
+                    Some(byte) => write!(line, "{:02x}", byte)?,
+                    None => line.push_str("  "),
+                }
+            }
+            line.push_str("  ");
+            line.extend(chunk.iter().map(|&byte| printable(byte)));
+            writeln!(io.stdout, "{}", line)?;
+        }
+        Ok(())
+    }
+}
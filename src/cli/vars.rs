@@ -0,0 +1,59 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+struct Variable {
+    value: String,
+    exported: bool, // passed on to scripts the shell runs
+}
+
+/// The shell variables, kept sorted by name.
+pub struct Variables {
+    vars: BTreeMap<String, Variable>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Variables { vars: BTreeMap::new() }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(|var| var.value.as_str())
+    }
+
+    /// Sets `name`, keeping it exported if it already was.
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.vars.get_mut(name) {
+            Some(var) => var.value = String::from(value),
+            None => {
+                self.vars.insert(String::from(name), Variable { value: String::from(value), exported: false });
+            }
+        }
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.vars.remove(name);
+    }
+
+    /// Marks `name` as exported, creating it empty if it does not exist.
+    pub fn export(&mut self, name: &str) {
+        self.vars
+            .entry(String::from(name))
+            .or_insert_with(|| Variable { value: String::new(), exported: false })
+            .exported = true;
+    }
+
+    /// Iterates over `(name, value)` pairs, only over exported variables if `exported_only` is set.
+    pub fn iter(&self, exported_only: bool) -> impl Iterator<Item = (&str, &str)> {
+        self.vars
+            .iter()
+            .filter(move |(_, var)| var.exported || !exported_only)
+            .map(|(name, var)| (name.as_str(), var.value.as_str()))
+    }
+}
+
+/// Whether `name` can be used as a variable name: a letter or `_`, then letters, digits or `_`.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
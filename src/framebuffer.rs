@@ -0,0 +1,337 @@
+use conquer_once::spin::OnceCell;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use x86_64::{
+    structures::paging::{
+        mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, PhysFrame, Size4KiB,
+    },
+    PhysAddr, VirtAddr,
+};
+use crate::vga_buffer::{cp437, Color};
+use self::font::Font;
+use self::vga::TextModeRegisters;
+
+pub mod font;    // Contains the PSF bitmap font text is drawn with
+pub mod console; // Contains the text console drawn on a framebuffer, written to like the VGA console
+mod pci;         // Contains the PCI configuration space reads that find the display adapter
+mod vbe;         // Contains the Bochs VBE registers that set the graphics mode
+mod vga;         // Contains the VGA registers saved on entering graphics mode and put back on leaving
+
+/// Virtual address `init` maps the video memory at.
+pub const FRAMEBUFFER_START: u64 = 0x_5555_5555_0000;
+/// Most video memory mapped, which bounds the modes `enter` accepts.
+pub const FRAMEBUFFER_MAX_SIZE: usize = 16 * 1024 * 1024; // 16 MiB
+
+// PCI IDs of the Bochs and QEMU standard VGA adapter
+const BOCHS_VENDOR: u16 = 0x1234;
+const BOCHS_DEVICE: u16 = 0x1111;
+// The text mode keeps its characters and font in the first 256 KiB of video memory,
+// so the framebuffer starts past them and the text is still there after `leave`
+const TEXT_MODE_MEMORY: usize = 256 * 1024;
+
+static VIDEO_MEMORY_SIZE: OnceCell<usize> = OnceCell::uninit(); // bytes mapped by `init`
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static TEXT_MODE: Mutex<Option<TextModeRegisters>> = Mutex::new(None);
+
+#[derive(Debug)]
+pub enum FramebufferError {
+    NoAdapter,                     // no Bochs or QEMU display adapter
+    NotMapped,                     // `init` has not mapped the video memory
+    Map(MapToError<Size4KiB>),
+    UnsupportedMode(usize, usize), // width and height
+    InUse,                         // the display is already in graphics mode
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FramebufferError::NoAdapter => write!(f, "no Bochs/QEMU display adapter"),
+            FramebufferError::NotMapped => write!(f, "video memory not mapped"),
+            FramebufferError::Map(e) => write!(f, "could not map video memory: {:?}", e),
+            FramebufferError::UnsupportedMode(width, height) => write!(f, "unsupported mode {}x{}", width, height),
+            FramebufferError::InUse => write!(f, "already in graphics mode"),
+        }
+    }
+}
+
+/// A color as 8-bit red, green and blue, packed the way 32-bit modes store pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Rgb(u32);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0x00, 0x00, 0x00);
+    pub const WHITE: Rgb = Rgb::new(0xff, 0xff, 0xff);
+
+    pub const fn new(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb((red as u32) << 16 | (green as u32) << 8 | blue as u32)
+    }
+}
+
+// The colors of the VGA text mode, indexed like `Color`
+const PALETTE: [Rgb; 16] = [
+    Rgb::new(0x00, 0x00, 0x00),
+    Rgb::new(0x00, 0x00, 0xaa),
+    Rgb::new(0x00, 0xaa, 0x00),
+    Rgb::new(0x00, 0xaa, 0xaa),
+    Rgb::new(0xaa, 0x00, 0x00),
+    Rgb::new(0xaa, 0x00, 0xaa),
+    Rgb::new(0xaa, 0x55, 0x00),
+    Rgb::new(0xaa, 0xaa, 0xaa),
+    Rgb::new(0x55, 0x55, 0x55),
+    Rgb::new(0x55, 0x55, 0xff),
+    Rgb::new(0x55, 0xff, 0x55),
+    Rgb::new(0x55, 0xff, 0xff),
+    Rgb::new(0xff, 0x55, 0x55),
+    Rgb::new(0xff, 0x55, 0xff),
+    Rgb::new(0xff, 0xff, 0x55),
+    Rgb::new(0xff, 0xff, 0xff),
+];
+
+impl From<Color> for Rgb {
+    fn from(color: Color) -> Rgb {
+        PALETTE[color as usize]
+    }
+}
+
+/// The pixels of a graphics mode, drawn into directly. Drawing is clipped to the screen.
+pub struct Framebuffer {
+    pixels: &'static mut [u32],
+    width: usize,
+    height: usize,
+    stride: usize, // pixels from the start of one row to the next
+}
+
+impl Framebuffer {
+    /// Wraps pixel memory laid out in rows `stride` pixels apart.
+    pub fn new(pixels: &'static mut [u32], width: usize, height: usize, stride: usize) -> Framebuffer {
+        assert!(stride >= width && pixels.len() >= stride * height, "pixel memory too small");
+        Framebuffer { pixels, width, height, stride }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The color at `(x, y)`, or None off the screen.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Rgb> {
+        (x < self.width && y < self.height).then(|| Rgb(self.pixels[y * self.stride + x]))
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Rgb) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.stride + x] = color.0;
+        }
+    }
+
+    pub fn fill(&mut self, color: Rgb) {
+        self.fill_rect(0, 0, self.width, self.height, color);
+    }
+
+    /// Fills the `width` by `height` rectangle whose top left corner is `(x, y)`.
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        let (right, bottom) = ((x + width).min(self.width), (y + height).min(self.height));
+        if x >= right {
+            return;
+        }
+        for row in y..bottom {
+            self.pixels[row * self.stride + x..row * self.stride + right].fill(color.0);
+        }
+    }
+
+    /// Draws the one pixel wide outline of a rectangle.
+    pub fn rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, y + height - 1, width, 1, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(x + width - 1, y, 1, height, color);
+    }
+
+    /// Draws a line from `(x0, y0)` to `(x1, y1)`, both ends included.
+    pub fn line(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: Rgb) {
+        // Bresenham's algorithm, stepping along both axes
+        let (mut x, mut y) = (x0 as isize, y0 as isize);
+        let (x1, y1) = (x1 as isize, y1 as isize);
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (step_x, step_y) = (if x < x1 { 1 } else { -1 }, if y < y1 { 1 } else { -1 });
+        let mut error = dx + dy;
+        loop {
+            self.set_pixel(x as usize, y as usize, color);
+            if x == x1 && y == y1 {
+                return;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Copies an image `width` pixels wide, stored row after row, with its top left corner at `(x, y)`.
+    pub fn blit(&mut self, x: usize, y: usize, width: usize, image: &[Rgb]) {
+        if width == 0 || x >= self.width {
+            return;
+        }
+        let visible = width.min(self.width - x);
+        for (row, line) in image.chunks(width).enumerate().take(self.height.saturating_sub(y)) {
+            let start = (y + row) * self.stride + x;
+            for (pixel, color) in self.pixels[start..start + visible.min(line.len())].iter_mut().zip(line) {
+                *pixel = color.0;
+            }
+        }
+    }
+
+    /// Draws glyph `index` of `font` in a cell with its top left corner at `(x, y)`.
+    pub fn draw_glyph(&mut self, x: usize, y: usize, font: &Font, index: usize, foreground: Rgb, background: Rgb) {
+        for (row, bits) in font.glyph(index).iter().enumerate() {
+            for col in 0..font.width() {
+                let color = if bits & (0x80 >> col) != 0 { foreground } else { background };
+                self.set_pixel(x + col, y + row, color);
+            }
+        }
+    }
+
+    /// Draws `text` in a single line starting at `(x, y)`, without interpreting control characters.
+    pub fn draw_text(&mut self, x: usize, y: usize, font: &Font, text: &str, foreground: Rgb, background: Rgb) {
+        for (index, character) in text.chars().enumerate() {
+            let glyph = cp437::encode(character) as usize;
+            self.draw_glyph(x + index * font.width(), y, font, glyph, foreground, background);
+        }
+    }
+
+    /// Moves the band of `height` rows starting at row `y` up by `lines` rows,
+    /// filling the rows freed at the bottom of the band.
+    pub fn scroll_up(&mut self, y: usize, height: usize, lines: usize, fill: Rgb) {
+        let bottom = (y + height).min(self.height);
+        let lines = lines.min(bottom.saturating_sub(y));
+        for row in y..bottom - lines {
+            let from = (row + lines) * self.stride;
+            self.pixels.copy_within(from..from + self.width, row * self.stride);
+        }
+        self.fill_rect(0, bottom - lines, self.width, lines, fill);
+    }
+}
+
+/// Finds the Bochs/QEMU display adapter and maps its video memory at `FRAMEBUFFER_START`,
+/// the way `init_heap` maps the heap. The display stays in text mode until `enter`.
+pub fn init(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), FramebufferError> {
+    let device = pci::find(BOCHS_VENDOR, BOCHS_DEVICE).ok_or(FramebufferError::NoAdapter)?;
+    let physical = device.bar0().ok_or(FramebufferError::NoAdapter)?;
+    if !vbe::is_present() {
+        return Err(FramebufferError::NoAdapter);
+    }
+    let size = vbe::video_memory_size().min(FRAMEBUFFER_MAX_SIZE);
+
+    // Video memory must not be cached, or drawing would only reach the screen on eviction
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE;
+    for offset in (0..size as u64).step_by(4096) {
+        let page = Page::containing_address(VirtAddr::new(FRAMEBUFFER_START + offset));
+        let frame = PhysFrame::containing_address(PhysAddr::new(physical + offset));
+        unsafe {
+            mapper
+                .map_to(page, frame, flags, frame_allocator)
+                .map_err(FramebufferError::Map)?
+                .flush()
+        };
+    }
+    VIDEO_MEMORY_SIZE.init_once(|| size);
+    Ok(())
+}
+
+/// Whether `init` found the adapter and mapped its memory.
+pub fn is_available() -> bool {
+    VIDEO_MEMORY_SIZE.is_initialized()
+}
+
+/// Whether the display is in the graphics mode `enter` set.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Switches the display to a `width` by `height` mode with 32 bits per pixel and
+/// returns its framebuffer, cleared to black.
+///
+/// The VGA consoles are not shown meanwhile; what is written to them before `leave`
+/// may be lost.
+pub fn enter(width: usize, height: usize) -> Result<Framebuffer, FramebufferError> {
+    let size = *VIDEO_MEMORY_SIZE.try_get().map_err(|_| FramebufferError::NotMapped)?;
+    if width == 0 || height == 0 || width > vbe::MAX_WIDTH || height > vbe::MAX_HEIGHT {
+        return Err(FramebufferError::UnsupportedMode(width, height));
+    }
+    let bytes_per_row = width * vbe::BITS_PER_PIXEL as usize / 8;
+    let first_row = TEXT_MODE_MEMORY.div_ceil(bytes_per_row);
+    if (first_row + height) * bytes_per_row > size {
+        return Err(FramebufferError::UnsupportedMode(width, height));
+    }
+    if ACTIVE.swap(true, Ordering::Acquire) {
+        return Err(FramebufferError::InUse);
+    }
+
+    *TEXT_MODE.lock() = Some(TextModeRegisters::save());
+    if !vbe::set_mode(width, height, first_row) {
+        restore_text_mode();
+        return Err(FramebufferError::UnsupportedMode(width, height));
+    }
+    let pixels = unsafe {
+        let start = (FRAMEBUFFER_START as *mut u32).add(first_row * width);
+        core::slice::from_raw_parts_mut(start, width * height)
+    };
+    let mut framebuffer = Framebuffer::new(pixels, width, height, width);
+    framebuffer.fill(Rgb::BLACK);
+    Ok(framebuffer)
+}
+
+/// Gives up the framebuffer `enter` returned and puts the text mode back.
+pub fn leave(_framebuffer: Framebuffer) {
+    restore_text_mode();
+}
+
+fn restore_text_mode() {
+    vbe::disable();
+    if let Some(registers) = TEXT_MODE.lock().take() {
+        registers.restore();
+    }
+    ACTIVE.store(false, Ordering::Release);
+}
+
+#[test_case]
+fn test_draw_primitives() {
+    use alloc::{boxed::Box, vec};
+    let pixels = Box::leak(vec![0u32; 12 * 8].into_boxed_slice());
+    let mut framebuffer = Framebuffer::new(pixels, 10, 8, 12);
+    let red = Rgb::new(0xff, 0, 0);
+
+    framebuffer.line(0, 0, 3, 3, red);
+    assert!((0..4).all(|i| framebuffer.pixel(i, i) == Some(red)));
+    assert_eq!(framebuffer.pixel(1, 0), Some(Rgb::BLACK));
+
+    framebuffer.rect(8, 6, 5, 5, Rgb::WHITE); // clipped at the right and bottom edges
+    assert_eq!(framebuffer.pixel(9, 6), Some(Rgb::WHITE));
+    assert_eq!(framebuffer.pixel(9, 7), Some(Rgb::BLACK));
+    assert_eq!(framebuffer.pixel(10, 6), None);
+
+    framebuffer.blit(8, 0, 3, &[red, Rgb::WHITE, red, Rgb::WHITE, red, Rgb::WHITE]);
+    assert_eq!(framebuffer.pixel(8, 0), Some(red));
+    assert_eq!(framebuffer.pixel(9, 1), Some(red));
+
+    framebuffer.scroll_up(0, 8, 1, Rgb::BLACK);
+    assert_eq!(framebuffer.pixel(0, 0), Some(Rgb::BLACK));
+    assert_eq!(framebuffer.pixel(1, 0), Some(red));
+    assert_eq!(framebuffer.pixel(9, 7), Some(Rgb::BLACK));
+}
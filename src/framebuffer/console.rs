@@ -0,0 +1,195 @@
+use core::fmt;
+use crate::vga_buffer::ansi::{Action, AnsiParser};
+use crate::vga_buffer::{cp437, Color, ColorCode, DEFAULT_COLOR};
+use super::font::Font;
+use super::{Framebuffer, Rgb, PALETTE};
+
+/// A text console drawn on a framebuffer with a bitmap font.
+///
+/// It takes the same text as `vga_buffer::Writer`, VT100 colors and cursor movement
+/// included, in as many rows and columns as the mode has room for. There is no
+/// cursor or scrollback. The framebuffer stays available for drawing around the text.
+pub struct Console {
+    framebuffer: Framebuffer,
+    font: Font,
+    columns: usize,
+    rows: usize,
+    column_position: usize,
+    row_position: usize,
+    color_code: ColorCode,
+    ansi: AnsiParser,
+    saved_cursor: (usize, usize, ColorCode), // row, column and colors kept by ESC 7
+}
+
+impl Console {
+    /// Starts a console at the top left corner of `framebuffer`.
+    pub fn new(framebuffer: Framebuffer, font: Font) -> Console {
+        let columns = (framebuffer.width() / font.width()).max(1);
+        let rows = (framebuffer.height() / font.height()).max(1);
+        Console {
+            framebuffer,
+            font,
+            columns,
+            rows,
+            column_position: 0,
+            row_position: 0,
+            color_code: DEFAULT_COLOR,
+            ansi: AnsiParser::new(),
+            saved_cursor: (0, 0, DEFAULT_COLOR),
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn framebuffer(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    pub fn into_framebuffer(self) -> Framebuffer {
+        self.framebuffer
+    }
+
+    /// Returns the row and column the next character will be written to.
+    pub fn position(&self) -> (usize, usize) {
+        (self.row_position, self.column_position)
+    }
+
+    /// Moves the insertion point anywhere on the screen.
+    pub fn set_position(&mut self, row: usize, col: usize) {
+        self.row_position = row.min(self.rows - 1);
+        self.column_position = col.min(self.columns - 1);
+    }
+
+    pub fn set_colors(&mut self, foreground: Color, background: Color) {
+        self.color_code = ColorCode::new(foreground, background);
+    }
+
+    /// Fills the screen with the background color and moves the insertion point to the top left corner.
+    pub fn clear_screen(&mut self) {
+        self.framebuffer.fill(self.background());
+        self.set_position(0, 0);
+    }
+
+    /// Writes `s`, acting on the VT100 escape sequences in it and showing other
+    /// characters with their code page 437 glyphs.
+    pub fn write_string(&mut self, s: &str) {
+        for character in s.chars() {
+            if !character.is_ascii() {
+                if self.ansi.is_idle() {
+                    self.put_glyph(cp437::encode(character));
+                }
+                continue;
+            }
+            match self.ansi.advance(character as u8) {
+                Some(Action::Print(b'\n')) => self.new_line(),
+                Some(Action::Print(b'\t')) => {
+                    for _ in 0..4 {
+                        self.put_glyph(b' ');
+                    }
+                }
+                Some(Action::Print(b'\x08')) => {
+                    if self.column_position > 0 {
+                        self.column_position -= 1;
+                        self.draw_cell(self.row_position, self.column_position, b' ');
+                    }
+                }
+                Some(Action::Print(byte @ 0x20..=0x7e)) => self.put_glyph(byte),
+                Some(Action::Print(_)) => self.put_glyph(cp437::REPLACEMENT),
+                Some(action) => self.apply(action),
+                None => {}
+            }
+        }
+    }
+
+    /// Carries out a control sequence. Cursor movement stops at the screen edges.
+    fn apply(&mut self, action: Action) {
+        let (row, col) = (self.row_position, self.column_position);
+        let (rows, columns) = (self.rows, self.columns);
+        match action {
+            Action::Print(byte) => self.put_glyph(byte),
+            Action::CursorUp(count) => self.row_position = row.saturating_sub(count),
+            Action::CursorDown(count) => self.row_position = (row + count).min(rows - 1),
+            Action::CursorForward(count) => self.column_position = (col + count).min(columns - 1),
+            Action::CursorBack(count) => self.column_position = col.min(columns - 1).saturating_sub(count),
+            Action::CursorTo(row, col) => self.set_position(row, col),
+            Action::EraseDisplay(mode) => {
+                let (first, last) = match mode {
+                    0 => (row + 1, rows),
+                    1 => (0, row),
+                    _ => (0, rows),
+                };
+                self.clear_cells(first, 0, last - first, columns);
+                if mode < 2 {
+                    self.apply(Action::EraseLine(mode));
+                }
+            }
+            Action::EraseLine(mode) => match mode {
+                0 => self.clear_cells(row, col, 1, columns - col.min(columns)),
+                1 => self.clear_cells(row, 0, 1, (col + 1).min(columns)),
+                _ => self.clear_cells(row, 0, 1, columns),
+            },
+            Action::SetGraphics(params) => self.color_code = self.color_code.with_graphics(&params),
+            Action::SaveCursor => self.saved_cursor = (row, col, self.color_code),
+            Action::RestoreCursor => {
+                let (row, col, color_code) = self.saved_cursor;
+                self.set_position(row, col);
+                self.color_code = color_code;
+            }
+        }
+    }
+
+    fn foreground(&self) -> Rgb {
+        PALETTE[self.color_code.foreground() as usize]
+    }
+
+    fn background(&self) -> Rgb {
+        PALETTE[self.color_code.background() as usize]
+    }
+
+    /// Draws glyph `byte` at the insertion point and moves past it, wrapping at the right edge.
+    fn put_glyph(&mut self, byte: u8) {
+        if self.column_position >= self.columns {
+            self.new_line();
+        }
+        self.draw_cell(self.row_position, self.column_position, byte);
+        self.column_position += 1;
+    }
+
+    fn draw_cell(&mut self, row: usize, col: usize, byte: u8) {
+        let (x, y) = (col * self.font.width(), row * self.font.height());
+        let (foreground, background) = (self.foreground(), self.background());
+        self.framebuffer.draw_glyph(x, y, &self.font, byte as usize, foreground, background);
+    }
+
+    /// Fills `count` rows of cells from `(row, col)`, `width` cells wide, with the background color.
+    fn clear_cells(&mut self, row: usize, col: usize, count: usize, width: usize) {
+        let (cell_width, cell_height) = (self.font.width(), self.font.height());
+        let background = self.background();
+        self.framebuffer.fill_rect(col * cell_width, row * cell_height, width * cell_width, count * cell_height, background);
+    }
+
+    fn new_line(&mut self) {
+        self.column_position = 0;
+        if self.row_position < self.rows - 1 {
+            self.row_position += 1;
+            return;
+        }
+        // Only the rows of whole cells scroll, so drawings below them stay put
+        let cell_height = self.font.height();
+        let background = self.background();
+        self.framebuffer.scroll_up(0, self.rows * cell_height, cell_height, background);
+    }
+}
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01; // 512 glyphs instead of 256
+const PSF1_HEADER_SIZE: usize = 4;
+
+/// The font drawn by default: 8x16 glyphs in code page 437 order, so the bytes
+/// `cp437::encode` returns index it directly. Rendered from DejaVu Sans Mono.
+pub static DEFAULT_FONT: &[u8] = include_bytes!("font.psf");
+
+/// A PSF1 bitmap font: glyphs 8 pixels wide, one byte per row, most significant bit on the left.
+#[derive(Debug, Clone, Copy)]
+pub struct Font {
+    glyphs: &'static [u8],
+    height: usize,
+    count: usize,
+}
+
+impl Font {
+    /// Reads a PSF1 font, or returns None if `data` is not one or is cut short.
+    pub fn parse(data: &'static [u8]) -> Option<Font> {
+        let header = data.get(..PSF1_HEADER_SIZE)?;
+        let (mode, height) = (header[2], header[3] as usize);
+        if header[..2] != PSF1_MAGIC || height == 0 {
+            return None;
+        }
+        let count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let glyphs = data.get(PSF1_HEADER_SIZE..PSF1_HEADER_SIZE + count * height)?;
+        Some(Font { glyphs, height, count })
+    }
+
+    pub fn width(&self) -> usize {
+        8
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The rows of glyph `index`, or of the replacement glyph past the end of the font.
+    pub fn glyph(&self, index: usize) -> &'static [u8] {
+        let index = if index < self.count { index } else { crate::vga_buffer::cp437::REPLACEMENT as usize };
+        &self.glyphs[index * self.height..(index + 1) * self.height]
+    }
+}
+
+impl Default for Font {
+    fn default() -> Font {
+        Font::parse(DEFAULT_FONT).expect("embedded font is valid")
+    }
+}
+
+#[test_case]
+fn test_parse_font() {
+    let font = Font::default();
+    assert_eq!((font.width(), font.height()), (8, 16));
+    assert!(font.glyph(b' ' as usize).iter().all(|&row| row == 0));
+    assert!(font.glyph(0xdb).iter().all(|&row| row == 0xff)); // █
+    assert!(font.glyph(b'A' as usize).iter().any(|&row| row != 0));
+    assert!(Font::parse(&DEFAULT_FONT[..100]).is_none());
+    assert!(Font::parse(b"not a font").is_none());
+}
@@ -0,0 +1,53 @@
+use x86_64::instructions::port::Port;
+
+const CONFIG_ADDRESS_PORT: u16 = 0xcf8;
+const CONFIG_DATA_PORT: u16 = 0xcfc;
+
+// Offsets in the configuration space of a device
+const VENDOR_DEVICE: u8 = 0x00;
+const BAR0: u8 = 0x10;
+
+const BAR_IO_SPACE: u32 = 0x01;
+const BAR_ADDRESS_MASK: u32 = !0x0f;
+
+/// A device found on the PCI bus, by its bus, slot and function numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Device {
+    bus: u8,
+    slot: u8,
+    function: u8,
+}
+
+impl Device {
+    fn read(&self, offset: u8) -> u32 {
+        let address = 1 << 31 // enable bit
+            | (self.bus as u32) << 16
+            | (self.slot as u32) << 11
+            | (self.function as u32) << 8
+            | (offset & 0xfc) as u32;
+        let mut address_port: Port<u32> = Port::new(CONFIG_ADDRESS_PORT);
+        let mut data_port: Port<u32> = Port::new(CONFIG_DATA_PORT);
+        unsafe {
+            address_port.write(address);
+            data_port.read()
+        }
+    }
+
+    /// The physical address of the memory behind BAR0, or None if it is an I/O port range.
+    pub fn bar0(&self) -> Option<u64> {
+        let bar = self.read(BAR0);
+        if bar & BAR_IO_SPACE != 0 {
+            return None;
+        }
+        Some((bar & BAR_ADDRESS_MASK) as u64)
+    }
+}
+
+/// Scans every bus and slot for the first device with the given IDs.
+///
+/// Only function 0 is checked, which is where display adapters sit.
+pub(super) fn find(vendor: u16, device: u16) -> Option<Device> {
+    (0..=255u8)
+        .flat_map(|bus| (0..32u8).map(move |slot| Device { bus, slot, function: 0 }))
+        .find(|candidate| candidate.read(VENDOR_DEVICE) == (device as u32) << 16 | vendor as u32)
+}
@@ -0,0 +1,82 @@
+use x86_64::instructions::port::Port;
+
+const INDEX_PORT: u16 = 0x1ce;
+const DATA_PORT: u16 = 0x1cf;
+
+// Registers behind the index port
+const INDEX_ID: u16 = 0x0;
+const INDEX_XRES: u16 = 0x1;
+const INDEX_YRES: u16 = 0x2;
+const INDEX_BPP: u16 = 0x3;
+const INDEX_ENABLE: u16 = 0x4;
+const INDEX_VIRT_WIDTH: u16 = 0x6;
+const INDEX_X_OFFSET: u16 = 0x8;
+const INDEX_Y_OFFSET: u16 = 0x9;
+const INDEX_VIDEO_MEMORY_64K: u16 = 0xa;
+
+// Interface versions; 0xb0c4 and later report the video memory size
+const ID_MIN: u16 = 0xb0c0;
+const ID_VIDEO_MEMORY: u16 = 0xb0c4;
+const ID_MAX: u16 = 0xb0c5;
+
+const ENABLED: u16 = 0x01;
+const LINEAR_FRAMEBUFFER: u16 = 0x40;
+const NO_CLEAR_MEMORY: u16 = 0x80; // the text mode font lives in video memory too
+
+/// Largest resolution the adapter accepts.
+pub(super) const MAX_WIDTH: usize = 2560;
+pub(super) const MAX_HEIGHT: usize = 1600;
+/// The only depth supported, giving one `u32` per pixel.
+pub(super) const BITS_PER_PIXEL: u16 = 32;
+
+fn read(index: u16) -> u16 {
+    let mut index_port: Port<u16> = Port::new(INDEX_PORT);
+    let mut data_port: Port<u16> = Port::new(DATA_PORT);
+    unsafe {
+        index_port.write(index);
+        data_port.read()
+    }
+}
+
+fn write(index: u16, value: u16) {
+    let mut index_port: Port<u16> = Port::new(INDEX_PORT);
+    let mut data_port: Port<u16> = Port::new(DATA_PORT);
+    unsafe {
+        index_port.write(index);
+        data_port.write(value);
+    }
+}
+
+/// Whether the Bochs display interface answers on its ports.
+pub(super) fn is_present() -> bool {
+    (ID_MIN..=ID_MAX).contains(&read(INDEX_ID))
+}
+
+/// Bytes of video memory, assuming the 4 MiB of old adapters if it cannot be asked.
+pub(super) fn video_memory_size() -> usize {
+    match read(INDEX_ID) {
+        id if id >= ID_VIDEO_MEMORY => read(INDEX_VIDEO_MEMORY_64K) as usize * 64 * 1024,
+        _ => 4 * 1024 * 1024,
+    }
+}
+
+/// Switches to a linear framebuffer mode whose first visible row is `first_row`
+/// rows into video memory. Returns whether the adapter took the mode.
+pub(super) fn set_mode(width: usize, height: usize, first_row: usize) -> bool {
+    write(INDEX_ENABLE, 0);
+    write(INDEX_XRES, width as u16);
+    write(INDEX_YRES, height as u16);
+    write(INDEX_BPP, BITS_PER_PIXEL);
+    write(INDEX_ENABLE, ENABLED | LINEAR_FRAMEBUFFER | NO_CLEAR_MEMORY);
+    write(INDEX_VIRT_WIDTH, width as u16);
+    write(INDEX_X_OFFSET, 0);
+    write(INDEX_Y_OFFSET, first_row as u16);
+    read(INDEX_XRES) as usize == width
+        && read(INDEX_YRES) as usize == height
+        && read(INDEX_Y_OFFSET) as usize == first_row
+}
+
+/// Turns the interface off, handing the display back to the VGA registers.
+pub(super) fn disable() {
+    write(INDEX_ENABLE, 0);
+}
@@ -0,0 +1,90 @@
+use x86_64::instructions::port::Port;
+
+const MISC_READ_PORT: u16 = 0x3cc;
+const MISC_WRITE_PORT: u16 = 0x3c2;
+const SEQUENCER_INDEX_PORT: u16 = 0x3c4;
+const SEQUENCER_DATA_PORT: u16 = 0x3c5;
+const CRTC_INDEX_PORT: u16 = 0x3d4;
+const CRTC_DATA_PORT: u16 = 0x3d5;
+const GRAPHICS_INDEX_PORT: u16 = 0x3ce;
+const GRAPHICS_DATA_PORT: u16 = 0x3cf;
+const ATTRIBUTE_PORT: u16 = 0x3c0; // takes an index, then a value, in turns
+const ATTRIBUTE_READ_PORT: u16 = 0x3c1;
+const INPUT_STATUS_PORT: u16 = 0x3da; // reading it sends the attribute port back to the index
+
+const SEQUENCER_RESET: u8 = 0x00;
+const CRTC_VERTICAL_RETRACE_END: u8 = 0x11;
+const CRTC_PROTECT: u8 = 0x80; // bit in the vertical retrace end register
+const ATTRIBUTE_PALETTE_SOURCE: u8 = 0x20; // turns the display back on after setting attributes
+
+/// The VGA registers of the text mode, which turning on the VBE interface overwrites.
+pub(super) struct TextModeRegisters {
+    misc: u8,
+    sequencer: [u8; 5],
+    crtc: [u8; 25],
+    graphics: [u8; 9],
+    attribute: [u8; 21],
+}
+
+fn read_indexed(index_port: u16, data_port: u16, index: u8) -> u8 {
+    unsafe {
+        Port::new(index_port).write(index);
+        Port::new(data_port).read()
+    }
+}
+
+fn write_indexed(index_port: u16, data_port: u16, index: u8, value: u8) {
+    unsafe {
+        Port::new(index_port).write(index);
+        Port::new(data_port).write(value);
+    }
+}
+
+fn reset_attribute_port() {
+    let mut status: Port<u8> = Port::new(INPUT_STATUS_PORT);
+    unsafe { status.read() };
+}
+
+impl TextModeRegisters {
+    pub fn save() -> Self {
+        let misc = unsafe { Port::new(MISC_READ_PORT).read() };
+        let sequencer = core::array::from_fn(|i| read_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_DATA_PORT, i as u8));
+        let crtc = core::array::from_fn(|i| read_indexed(CRTC_INDEX_PORT, CRTC_DATA_PORT, i as u8));
+        let graphics = core::array::from_fn(|i| read_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_DATA_PORT, i as u8));
+        let attribute = core::array::from_fn(|i| {
+            reset_attribute_port();
+            read_indexed(ATTRIBUTE_PORT, ATTRIBUTE_READ_PORT, i as u8)
+        });
+        reset_attribute_port();
+        unsafe { Port::new(ATTRIBUTE_PORT).write(ATTRIBUTE_PALETTE_SOURCE) };
+        TextModeRegisters { misc, sequencer, crtc, graphics, attribute }
+    }
+
+    pub fn restore(&self) {
+        unsafe { Port::new(MISC_WRITE_PORT).write(self.misc) };
+        // The sequencer is held in reset while its clocking changes
+        write_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_DATA_PORT, SEQUENCER_RESET, 0x01);
+        for (index, &value) in self.sequencer.iter().enumerate().skip(1) {
+            write_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_DATA_PORT, index as u8, value);
+        }
+        write_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_DATA_PORT, SEQUENCER_RESET, self.sequencer[0]);
+        // CRTC registers 0 to 7 are read-only until the protect bit is cleared
+        let retrace_end = self.crtc[CRTC_VERTICAL_RETRACE_END as usize];
+        write_indexed(CRTC_INDEX_PORT, CRTC_DATA_PORT, CRTC_VERTICAL_RETRACE_END, retrace_end & !CRTC_PROTECT);
+        for (index, &value) in self.crtc.iter().enumerate() {
+            write_indexed(CRTC_INDEX_PORT, CRTC_DATA_PORT, index as u8, value);
+        }
+        for (index, &value) in self.graphics.iter().enumerate() {
+            write_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_DATA_PORT, index as u8, value);
+        }
+        let mut attribute_port: Port<u8> = Port::new(ATTRIBUTE_PORT);
+        reset_attribute_port();
+        for (index, &value) in self.attribute.iter().enumerate() {
+            unsafe {
+                attribute_port.write(index as u8);
+                attribute_port.write(value);
+            }
+        }
+        unsafe { attribute_port.write(ATTRIBUTE_PALETTE_SOURCE) };
+    }
+}
@@ -1,11 +1,41 @@
-use spin::Mutex;
+use core::future::Future;
+use spin::RwLock;
 use crate::FileTable;
+use crate::fs::lock::LockTable;
 pub trait BlockDevice {
     const BLOCK_SIZE: usize = 512;
     fn read_block(&self, block_id: usize, data_size: usize, buf: &mut [u8]);
-    fn write_block(&mut self, block_id: usize, buf: &[u8]);
-    fn get_file_table(&mut self) -> &mut Mutex<FileTable>;  
-    fn get_file_table_immutable(&self) -> &Mutex<FileTable> ;
+    fn write_block(&self, block_id: usize, buf: &[u8]);
 
+    /// Reads `buf.len()` bytes from consecutive blocks starting at `start_block`.
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) {
+        for (i, chunk) in buf.chunks_mut(Self::BLOCK_SIZE).enumerate() {
+            self.read_block(start_block + i, chunk.len(), chunk);
+        }
+    }
 
+    /// Writes `buf` to consecutive blocks starting at `start_block`.
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+        for (i, chunk) in buf.chunks(Self::BLOCK_SIZE).enumerate() {
+            self.write_block(start_block + i, chunk);
+        }
+    }
+
+    /// The file table; readers share it, creating, resizing or removing files takes it exclusively.
+    fn get_file_table(&self) -> &RwLock<FileTable>;
+    /// Per-inode locks held by the file operations while they access file data.
+    fn get_inode_locks(&self) -> &LockTable;
+    /// Advisory `flock`-style locks taken explicitly through `OpenFile`.
+    fn get_flocks(&self) -> &LockTable;
+}
+
+/// Block I/O whose completion is awaited instead of waited for.
+///
+/// An interrupt-driven driver would start the transfer and return a future that its
+/// interrupt handler wakes, so the calling task sleeps on the executor while the disk works.
+pub trait AsyncBlockDevice: BlockDevice {
+    /// Reads `buf.len()` bytes from consecutive blocks starting at `start_block`.
+    fn read_blocks_async<'a>(&'a self, start_block: usize, buf: &'a mut [u8]) -> impl Future<Output = ()> + 'a;
+    /// Writes `buf` to consecutive blocks starting at `start_block`.
+    fn write_blocks_async<'a>(&'a self, start_block: usize, buf: &'a [u8]) -> impl Future<Output = ()> + 'a;
 }
@@ -1,17 +1,23 @@
-use crate::fs::block_device::BlockDevice;
+use crate::fs::block_device::{AsyncBlockDevice, BlockDevice};
+use crate::fs::lock::LockTable;
 use super::file_table::FileTable;
-use spin::Mutex;
+use omega::task::yield_now;
+use spin::{Mutex, RwLock};
 pub struct MyBlockDevice {
-    storage: &'static mut [u8],
-    files_table: Mutex<FileTable>, // Mutex to protect access to the file table
+    storage: Mutex<&'static mut [u8]>, // only held for the duration of a single copy
+    files_table: RwLock<FileTable>,    // RwLock to protect access to the file table
+    inode_locks: LockTable,
+    flocks: LockTable,
 }
 const BLOCKS_AMOUNT: usize = 1024;
 impl MyBlockDevice {
     pub fn new(storage: &'static mut [u8]) -> Self {
         let files_table = FileTable::new(BLOCKS_AMOUNT);
         Self {
-            storage,
-            files_table: Mutex::new(files_table), // Initialize Mutex
+            storage: Mutex::new(storage),
+            files_table: RwLock::new(files_table), // Initialize RwLock
+            inode_locks: LockTable::new(),
+            flocks: LockTable::new(),
         }
     }
     
@@ -24,23 +30,49 @@ impl BlockDevice for MyBlockDevice {
     fn read_block(&self, block_id: usize, data_size: usize, buf: &mut [u8]) {
         let start = block_id * Self::BLOCK_SIZE;
         let end = start + data_size;
-        buf[..data_size].copy_from_slice(&self.storage[start..end]);
+        buf[..data_size].copy_from_slice(&self.storage.lock()[start..end]);
 
     }
     
-    fn write_block(&mut self, block_id: usize, buf: &[u8]) {
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
         let start = block_id * Self::BLOCK_SIZE;
         let end = start + buf.len();  
-        self.storage[start..end].copy_from_slice(buf);
+        self.storage.lock()[start..end].copy_from_slice(buf);
     }
-    
-    fn get_file_table(&mut self) -> &mut Mutex<FileTable> {
-        &mut self.files_table // Return a mutable reference to the Mutex
+
+    // The storage is contiguous, so a whole extent is a single copy
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) {
+        self.read_block(start_block, buf.len(), buf);
+    }
+
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+        self.write_block(start_block, buf);
     }
-    fn get_file_table_immutable(&self) -> &Mutex<FileTable> {
-        // Implementation depends on your design
+    
+    fn get_file_table(&self) -> &RwLock<FileTable> {
         &self.files_table
     }
 
+    fn get_inode_locks(&self) -> &LockTable {
+        &self.inode_locks
+    }
+
+    fn get_flocks(&self) -> &LockTable {
+        &self.flocks
+    }
+
 }
 
+// The RAM disk raises no interrupts: a transfer is done as soon as the copy returns,
+// so yield afterwards to keep long reads and writes from starving the other tasks.
+impl AsyncBlockDevice for MyBlockDevice {
+    async fn read_blocks_async(&self, start_block: usize, buf: &mut [u8]) {
+        self.read_blocks(start_block, buf);
+        yield_now().await;
+    }
+
+    async fn write_blocks_async(&self, start_block: usize, buf: &[u8]) {
+        self.write_blocks(start_block, buf);
+        yield_now().await;
+    }
+}
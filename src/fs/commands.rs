@@ -0,0 +1,187 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::pin::pin;
+use futures_util::future::{select, Either};
+use omega::keyboard::{interrupted, wait_for_interrupt};
+use omega::println;
+use spin::Mutex;
+use crate::cli::Shell;
+use crate::cli::command::{Args, Command, CommandError};
+use crate::cli::io::Io;
+use crate::cli::registry::register;
+use crate::fs::buffer::MyBlockDevice;
+use crate::fs::lock::LockKind;
+use crate::fs::open_file::OpenFile;
+use crate::fs::vfs::{
+    create_file, delete_file, link_file, list_files, open_file, read_file, symlink_file, symlink_target, write_file,
+};
+
+// Files the shell holds advisory locks on through `flock`, until `flock -u`
+static HELD_LOCKS: Mutex<Vec<OpenFile<'static, MyBlockDevice>>> = Mutex::new(Vec::new());
+
+/// Registers the shell commands that work on files.
+pub fn register_commands() {
+    register(&Touch);
+    register(&Wf);
+    register(&Rm);
+    register(&Cat);
+    register(&Ln);
+    register(&Flock);
+    register(&Ls);
+}
+
+struct Touch;
+
+impl Command for Touch {
+    fn name(&self) -> &'static str { "touch" }
+    fn usage(&self) -> &'static str { "touch <file>" }
+    fn help(&self) -> &'static str { "Create an empty file" }
+    fn args(&self) -> Args { Args::Exactly(1) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        Ok(create_file(args[0])?)
+    }
+}
+
+struct Wf;
+
+impl Command for Wf {
+    fn name(&self) -> &'static str { "wf" }
+    fn usage(&self) -> &'static str { "wf <file>" }
+    fn help(&self) -> &'static str { "Replace the contents of a file with its input, or lines typed up to Ctrl+D" }
+    fn args(&self) -> Args { Args::Exactly(1) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        if io.stdin.is_console() {
+            println!("Enter data for file, then Ctrl+D:"); // the prompt stays on the console even when output is redirected
+        }
+        let data = io.stdin.read_to_end().await;
+        if interrupted() {
+            return Err(CommandError::Interrupted); // Ctrl+C leaves the file as it was
+        }
+        Ok(write_file(args[0], &data).await?)
+    }
+}
+
+struct Rm;
+
+impl Command for Rm {
+    fn name(&self) -> &'static str { "rm" }
+    fn aliases(&self) -> &'static [&'static str] { &["del"] }
+    fn usage(&self) -> &'static str { "rm <file>" }
+    fn help(&self) -> &'static str { "Remove a file" }
+    fn args(&self) -> Args { Args::Exactly(1) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        writeln!(io.stdout, "Removing file: {}", args[0])?;
+        Ok(delete_file(args[0]).await?)
+    }
+}
+
+struct Cat;
+
+impl Command for Cat {
+    fn name(&self) -> &'static str { "cat" }
+    fn usage(&self) -> &'static str { "cat <file>" }
+    fn help(&self) -> &'static str { "Print the contents of a file" }
+    fn args(&self) -> Args { Args::Exactly(1) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let data = read_file(args[0]).await?;
+        io.stdout.write_bytes(&data);
+        if !data.ends_with(b"\n") {
+            io.stdout.write_bytes(b"\n");
+        }
+        Ok(())
+    }
+}
+
+struct Ln;
+
+impl Command for Ln {
+    fn name(&self) -> &'static str { "ln" }
+    fn usage(&self) -> &'static str { "ln [-s] <target> <link>" }
+    fn help(&self) -> &'static str { "Add a hard link to a file, or a symbolic link with -s" }
+    fn args(&self) -> Args { Args::Range(2, 3) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        match args {
+            [target, link] => Ok(link_file(target, link)?),
+            ["-s", target, link] => Ok(symlink_file(target, link)?),
+            _ => Err(CommandError::Usage),
+        }
+    }
+}
+
+struct Flock;
+
+impl Command for Flock {
+    fn name(&self) -> &'static str { "flock" }
+    fn usage(&self) -> &'static str { "flock [-n] -s|-x|-u <file>" }
+    fn help(&self) -> &'static str {
+        "Take a shared or exclusive advisory lock on a file, waiting for it until Ctrl+C unless -n is given, or release it"
+    }
+    fn args(&self) -> Args { Args::Range(2, 3) }
+
+    async fn run(&self, _shell: &mut Shell, _io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let (wait, mode, filename) = match args {
+            ["-n", mode, filename] => (false, *mode, *filename),
+            [mode, filename] => (true, *mode, *filename),
+            _ => return Err(CommandError::Usage),
+        };
+        // Advisory locks are only kept by the block device, so /tmp files give Unsupported
+        let opened = open_file(filename)?;
+        let kind = match mode {
+            "-s" => LockKind::Shared,
+            "-x" => LockKind::Exclusive,
+            "-u" => {
+                HELD_LOCKS.lock().retain(|file| file.ino() != opened.ino());
+                return Ok(());
+            }
+            _ => return Err(CommandError::Usage),
+        };
+        // Locking a file the shell already holds converts its lock
+        let mut file = {
+            let mut held_locks = HELD_LOCKS.lock();
+            let index = held_locks.iter().position(|file| file.ino() == opened.ino());
+            index.map_or(opened, |index| held_locks.remove(index))
+        };
+        if !wait {
+            file.try_flock(kind)?;
+        } else if let Either::Right(_) = select(pin!(file.flock(kind)), pin!(wait_for_interrupt())).await {
+            // The lock may be held by the shell itself, so it might never come; a file
+            // whose lock was being converted is left unlocked
+            return Err(CommandError::Interrupted);
+        }
+        HELD_LOCKS.lock().push(file);
+        Ok(())
+    }
+}
+
+struct Ls;
+
+impl Command for Ls {
+    fn name(&self) -> &'static str { "ls" }
+    fn aliases(&self) -> &'static [&'static str] { &["dir"] }
+    fn usage(&self) -> &'static str { "ls [dir]" }
+    fn help(&self) -> &'static str { "List the files of a mounted filesystem" }
+    fn args(&self) -> Args { Args::Range(0, 1) }
+
+    async fn run(&self, _shell: &mut Shell, io: &mut Io, args: &[&str]) -> Result<(), CommandError> {
+        let dir = args.first().copied().unwrap_or("/");
+        let files = list_files(dir)?;
+        if files.is_empty() {
+            writeln!(io.stdout, "No files found.")?;
+            return Ok(());
+        }
+        writeln!(io.stdout, "Files:")?;
+        for file in files {
+            match symlink_target(&format!("{}/{}", dir.trim_end_matches('/'), file)) {
+                Some(target) => writeln!(io.stdout, "- {} -> {}", file, target)?,
+                None => writeln!(io.stdout, "- {}", file)?,
+            }
+        }
+        Ok(())
+    }
+}
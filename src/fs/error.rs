@@ -0,0 +1,34 @@
+use core::fmt;
+
+/// Errors returned by the filesystem operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,           // no entry with that name
+    AlreadyExists,      // an entry with that name is already present
+    NameTooLong,        // the name does not fit in `FileEntry::name`
+    InvalidName,        // the name is empty or contains '/'
+    NoSpace,            // no free blocks or inodes left
+    FileTooLarge,       // the file needs more extents than an inode can map
+    SymlinkLoop,        // too many symbolic links followed while resolving a name
+    Busy,               // the file is locked by another operation
+    Unsupported,        // the filesystem does not implement the operation
+    CrossDevice,        // a hard link would span two filesystems
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            FsError::NotFound => "File not found",
+            FsError::AlreadyExists => "File already exists",
+            FsError::NameTooLong => "File name too long",
+            FsError::InvalidName => "Invalid file name",
+            FsError::NoSpace => "No available blocks",
+            FsError::FileTooLarge => "File too large",
+            FsError::SymlinkLoop => "Too many levels of symbolic links",
+            FsError::Busy => "Resource busy",
+            FsError::Unsupported => "Operation not supported",
+            FsError::CrossDevice => "Invalid cross-device link",
+        };
+        f.write_str(message)
+    }
+}
@@ -0,0 +1,62 @@
+use alloc::vec::Vec;
+use crate::fs::block_device::BlockDevice;
+use super::file_table::Inode;
+
+/// Number of extents stored directly in an inode before spilling to an indirect block.
+pub const INLINE_EXTENTS: usize = 4;
+/// Size in bytes of one extent record inside an indirect block.
+const EXTENT_RECORD_SIZE: usize = 8;
+/// Number of extent records that fit into one indirect block.
+pub const EXTENTS_PER_BLOCK: usize = 512 / EXTENT_RECORD_SIZE;
+
+/// A run of `len` contiguous blocks starting at block `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    pub start: usize,
+    pub len: usize,
+}
+
+impl Extent {
+    pub fn new(start: usize, len: usize) -> Self {
+        Self { start, len }
+    }
+
+    /// Returns the id of the first block after this extent.
+    pub fn end(&self) -> usize {
+        self.start + self.len
+    }
+}
+
+/// Serializes extent records into the layout of an indirect block: `start` and `len` as little-endian `u32`s.
+pub fn encode_extents(extents: &[Extent]) -> [u8; 512] {
+    let mut buffer = [0u8; 512];
+    for (record, extent) in buffer.chunks_exact_mut(EXTENT_RECORD_SIZE).zip(extents) {
+        record[..4].copy_from_slice(&(extent.start as u32).to_le_bytes());
+        record[4..].copy_from_slice(&(extent.len as u32).to_le_bytes());
+    }
+    buffer
+}
+
+/// Parses the records of an indirect block, stopping at the first empty one.
+pub fn decode_extents(buffer: &[u8]) -> Vec<Extent> {
+    buffer
+        .chunks_exact(EXTENT_RECORD_SIZE)
+        .map(|record| {
+            let start = u32::from_le_bytes([record[0], record[1], record[2], record[3]]) as usize;
+            let len = u32::from_le_bytes([record[4], record[5], record[6], record[7]]) as usize;
+            Extent::new(start, len)
+        })
+        .take_while(|extent| extent.len != 0)
+        .collect()
+}
+
+/// Returns every extent of `inode`, reading its indirect block from `device` if it has one.
+pub fn load_extents<T: BlockDevice>(device: &T, inode: &Inode) -> Vec<Extent> {
+    let mut extents = inode.extents.clone();
+    if let Some(block) = inode.indirect {
+        let mut buffer = [0u8; 512];
+        device.read_block(block, buffer.len(), &mut buffer);
+        extents.extend(decode_extents(&buffer));
+    }
+    extents
+}
@@ -1,11 +1,14 @@
-use crate::fs::block_device::BlockDevice;
+use crate::fs::block_device::{AsyncBlockDevice, BlockDevice};
 use crate::fs::superblock::Superblock;
+use crate::fs::error::FsError;
+use crate::fs::extent::{encode_extents, load_extents, Extent};
+use crate::fs::file_table::FileTable;
+use crate::fs::lock::{LockGuard, LockKind};
 use super::buffer::MyBlockDevice;
-use omega::println;
-use super::file_table::{self, FileTable};
+use alloc::vec;
 use alloc::vec::Vec;
 
-pub fn format_fs<T: BlockDevice>(device: &mut T) {
+pub fn format_fs<T: BlockDevice>(device: &T) {
     let superblock = Superblock::new(1024); 
 
     
@@ -24,84 +27,151 @@ pub fn format_fs<T: BlockDevice>(device: &mut T) {
 }
 
 
-pub fn create_file(device: &mut MyBlockDevice, filename: &str) {
-    // Lock the file_table to prevent race conditions
-    {
-    let mut files_table = device.get_file_table().lock();
+pub fn create_file(device: &MyBlockDevice, filename: &str) -> Result<(), FsError> {
+    // Take the file_table exclusively to prevent race conditions
+    let mut files_table = device.get_file_table().write();
     // Add the file entry to the file table
-    files_table.add_file(filename);
+    files_table.add_file(filename)
+}
+
+/// Creates `link_name` as a hard link sharing the inode of `target`.
+pub fn link_file<T: BlockDevice>(device: &T, target: &str, link_name: &str) -> Result<(), FsError> {
+    device.get_file_table().write().add_link(target, link_name)
+}
+
+/// Creates `link_name` as a symbolic link pointing at the path `target`.
+pub fn symlink_file<T: BlockDevice>(device: &T, target: &str, link_name: &str) -> Result<(), FsError> {
+    device.get_file_table().write().add_symlink(target, link_name)
+}
+
+/// Looks up an inode with `resolve` and waits for its lock.
+///
+/// The table is not held while waiting, so the lookup is repeated once the lock is
+/// taken, and the wait starts over if the name was removed or relinked meanwhile.
+async fn lock_inode<'a, T: BlockDevice>(
+    device: &'a T,
+    kind: LockKind,
+    resolve: impl Fn(&FileTable) -> Result<usize, FsError>,
+) -> Result<(usize, LockGuard<'a>), FsError> {
+    loop {
+        let ino = resolve(&device.get_file_table().read())?;
+        let guard = device.get_inode_locks().lock(ino, kind).await;
+        if resolve(&device.get_file_table().read())? == ino {
+            return Ok((ino, guard));
+        }
     }
 }
 
-pub fn write_file<T: BlockDevice>(device: &mut T, file_name: &str, data: &[u8]) {     
-    // TODO: make the function support more than 512 bytes by dividing into multiple blocks
-    let mut buffer = [0u8; 512];
-    buffer[..data.len()].copy_from_slice(data);
+/// Replaces the contents of `file_name` with `data`.
+///
+/// Holds the inode exclusively for the whole write, waiting on the executor while
+/// another operation is using the file or the device is busy.
+pub async fn write_file<T: AsyncBlockDevice>(device: &T, file_name: &str, data: &[u8]) -> Result<(), FsError> {     
+    // Every file keeps at least its first block
+    let blocks = data.len().div_ceil(T::BLOCK_SIZE).max(1);
 
-    // Lock the file table to find the file's blocks
-    let block = {
-        let mut file_table = device.get_file_table().lock(); // Lock file table
-        
-        // Find the file entry by exact name match
-        let file_entry = file_table.entries.iter_mut()
-            .find(|entry| {
-                // Convert entry name to string and trim null bytes
-                let entry_name = core::str::from_utf8(&entry.name)
-                    .unwrap_or("")
-                    .trim_end_matches('\0');
-                
-                // Compare exact names
-                entry_name == file_name
-            });
+    let (ino, _inode_lock) = lock_inode(device, LockKind::Exclusive, |table| table.resolve(file_name)).await?;
+
+    // Take the file table exclusively only to map the new size onto extents
+    let (extents, indirect) = {
+        let mut file_table = device.get_file_table().write();
         
-        // Update size and get block in one pass
-        if let Some(entry) = file_entry {
-            entry.size = data.len();
-            entry.blocks.first().copied()
-        } else {
-            println!("File not found");
-            None
-        }
+        let mut extents = load_extents(device, file_table.inode(ino)?);
+        let indirect = file_table.resize_inode(ino, &mut extents, blocks)?;
+        file_table.inode_mut(ino)?.size = data.len();
+        (extents, indirect)
     };
 
-    // Once the lock is released, perform the write operation
-    if let Some(block) = block {
-        device.write_block(block, &buffer); 
-    } 
+    // Once the table is released, perform the write operations under the inode lock
+    if let Some((block, records)) = indirect {
+        device.write_blocks_async(block, &encode_extents(&records)).await;
+    }
+    let mut offset = 0;
+    for extent in extents {
+        let end = (offset + extent.len * T::BLOCK_SIZE).min(data.len());
+        let whole = offset + (end - offset) / T::BLOCK_SIZE * T::BLOCK_SIZE;
+        // The full blocks of an extent go out in one multi-block write
+        if whole > offset {
+            device.write_blocks_async(extent.start, &data[offset..whole]).await;
+        }
+        // The final partial block is padded with zeroes
+        if end > whole {
+            let mut buffer = [0u8; 512];
+            buffer[..end - whole].copy_from_slice(&data[whole..end]);
+            device.write_blocks_async(extent.start + (whole - offset) / T::BLOCK_SIZE, &buffer).await;
+        }
+        offset = end;
+    }
+    Ok(())
 }
 
 
 
-pub fn read_file<T: BlockDevice>(device: &T, file_name: &str) -> Option<Vec<u8>> {     // TODO: make the function support more than 512 bytes by dividing into multiple blocks
+/// Reads the whole contents of `file_name` while holding its inode shared.
+pub async fn read_file<T: AsyncBlockDevice>(device: &T, file_name: &str) -> Result<Vec<u8>, FsError> {
 
-    let file_table = device.get_file_table_immutable().lock();
-    if let Some(file_entry) = file_table.find_file(file_name) {
-        let size = file_entry.size;
-
-        let mut buffer = Vec::with_capacity(size); 
-        unsafe { buffer.set_len(size); } 
+    let (ino, _inode_lock) = lock_inode(device, LockKind::Shared, |table| table.resolve(file_name)).await?;
+    // The table is released before awaiting the reads, the inode lock keeps the extents stable
+    let (size, extents) = {
+        let file_table = device.get_file_table().read();
+        let inode = file_table.inode(ino)?;
+        (inode.size, load_extents(device, inode))
+    };
 
-        device.read_block(file_entry.blocks[0], size, &mut buffer);
+    let mut buffer = vec![0u8; size];
 
-        Some(buffer)
-    } else {
-        None
+    // One multi-block read per extent
+    let mut offset = 0;
+    for extent in extents {
+        if offset >= size {
+            break;
+        }
+        let end = (offset + extent.len * T::BLOCK_SIZE).min(size);
+        device.read_blocks_async(extent.start, &mut buffer[offset..end]).await;
+        offset = end;
     }
-}
 
-pub fn delete_file<T: BlockDevice>(device: &mut T, file_name: &str) {
-    let blocks_to_delete = {
-        let file_table = device.get_file_table();
-        let mut locked_table = file_table.lock();
-        
+    Ok(buffer)
+}
 
-        locked_table.find_and_remove_file(file_name)
+/// Removes the entry `file_name`. Its blocks are only zeroed and freed once the last link is gone.
+pub async fn delete_file<T: AsyncBlockDevice>(device: &T, file_name: &str) -> Result<(), FsError> {
+    // The entry itself is removed, so symbolic links are not followed here
+    let entry_inode = |table: &FileTable| {
+        let index = table.lookup(file_name).ok_or(FsError::NotFound)?;
+        Ok(table.entries[index].inode)
+    };
+    let (_, _inode_lock) = lock_inode(device, LockKind::Exclusive, entry_inode).await?;
+    let released = device.get_file_table().write().find_and_remove_file(file_name)?;
+    let Some(inode) = released else {
+        return Ok(()); // other links still use the blocks
     };
+
+    let mut extents_to_delete = load_extents(device, &inode);
+    extents_to_delete.extend(inode.indirect.map(|block| Extent::new(block, 1)));
     
-    if let Some(blocks) = blocks_to_delete {
-        let empty_block = [0u8; 512]; 
-        for &block in &blocks {
-            device.write_block(block, &empty_block);
+    // Zero the blocks before handing them back to the allocator
+    let empty_block = [0u8; 512]; 
+    for extent in &extents_to_delete {
+        for block in extent.start..extent.end() {
+            device.write_blocks_async(block, &empty_block).await;
         }
     }
+    let mut file_table = device.get_file_table().write();
+    for extent in extents_to_delete {
+        file_table.free_extent(extent);
+    }
+    Ok(())
+}
+
+#[test_case]
+fn test_write_then_read_file() {
+    use omega::task::block_on;
+    let device = crate::DEVICE.lock().expect("the device is mounted before the tests run");
+    create_file(device, "test_write_then_read").unwrap();
+    let data = [7u8; 700]; // one full block and a partial one
+    block_on(write_file(device, "test_write_then_read", &data)).unwrap();
+    assert_eq!(block_on(read_file(device, "test_write_then_read")).unwrap(), data);
+    block_on(delete_file(device, "test_write_then_read")).unwrap();
+    assert_eq!(block_on(read_file(device, "test_write_then_read")), Err(FsError::NotFound));
 }
\ No newline at end of file
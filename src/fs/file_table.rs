@@ -1,121 +1,364 @@
+use alloc::string::String;
 use alloc::vec::Vec;
-use omega::println;
 use crate::fs::block_device::BlockDevice;
+use crate::fs::error::FsError;
+use crate::fs::extent::{self, Extent, EXTENTS_PER_BLOCK, INLINE_EXTENTS};
+
+/// Maximum number of symbolic links followed while resolving a single name.
+pub const MAX_SYMLINK_DEPTH: usize = 8;
+/// Longest file name in bytes, the size of `FileEntry::name`.
+pub const MAX_NAME_LEN: usize = 16;
+
+/// What the data of an inode represents.
+pub enum InodeKind {
+    File,
+    Symlink(String), // the path the link points to
+}
+
+/// The file itself: its data blocks and size, shared by every entry linking to it.
+pub struct Inode {
+    pub kind: InodeKind,
+    pub extents: Vec<Extent>,   // first `INLINE_EXTENTS` runs of blocks holding the data, in file order
+    pub indirect: Option<usize>, // block holding the remaining extent records, if any
+    pub size: usize,            // file size in bytes
+    pub links: usize,           // number of entries referring to this inode
+}
+
+/// A named entry in the file table pointing at an inode.
 #[repr(C)]
 pub struct FileEntry {
-    pub name: [u8; 16],   
-    pub blocks: Vec<usize>, // all blocks of the file by id
-    pub size: usize,        // file size in bytes
+    pub name: [u8; MAX_NAME_LEN],
+    pub inode: usize,     // index into `FileTable::inodes`
     pub flags: u8,        
 }
 
 impl FileEntry {
-    pub fn new(name: &str, start_block: usize) -> Self {
-        let mut name_buf = [0u8; 16];
+    pub fn new(name: &str, inode: usize) -> Self {
+        let mut name_buf = [0u8; MAX_NAME_LEN];
         let bytes = name.as_bytes();
         name_buf[..bytes.len()].copy_from_slice(bytes);
 
         Self {
             name: name_buf,
-            blocks: Vec::from([start_block]), 
-            size: 0,
+            inode,
             flags: 0,
         }
     }
+
+    /// Returns the entry name with the null padding trimmed.
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name).unwrap_or("").trim_end_matches('\0')
+    }
 }
 pub struct FileTable {
     pub entries: Vec<FileEntry>,  
-    pub available_blocks: Vec<usize>
+    pub inodes: Vec<Option<Inode>>, // `None` marks a free inode slot
+    pub free_extents: Vec<Extent>,  // free runs of blocks, sorted by start and never adjacent
 }
 impl FileTable {
-    pub fn find_and_remove_file(&mut self, file_name: &str) -> Option<Vec<usize>> {
+    /// Removes the entry `file_name` and drops one link from its inode.
+    ///
+    /// Returns the inode once its last link is gone. Its blocks are still allocated;
+    /// the caller releases them with `free_extent` after loading the extents.
+    pub fn find_and_remove_file(&mut self, file_name: &str) -> Result<Option<Inode>, FsError> {
         // Find the file entry
-        let index = self.entries.iter().position(|entry| {
-            let name_str = core::str::from_utf8(&entry.name).unwrap_or("").trim_end_matches('\0');
-            name_str == file_name
-        })?;
-        
-        // Get the blocks
-        let blocks = self.entries[index].blocks.clone();
-        
+        let index = self.lookup(file_name).ok_or(FsError::NotFound)?;
+
         // Remove the entry
-        self.entries.remove(index);
-        
-        // Add blocks to available pool
-        self.available_blocks.extend(blocks.clone());
-        
-        Some(blocks)
+        let ino = self.entries.remove(index).inode;
+
+        let inode = self.inodes[ino].as_mut().expect("entry points at a free inode");
+        inode.links -= 1;
+        if inode.links > 0 {
+            return Ok(None);
+        }
+
+        // Last link is gone: release the inode slot
+        Ok(self.inodes[ino].take())
     }
 
     pub fn new(blocks_amount: usize) -> Self {
         FileTable {
             entries: Vec::new(),
-            available_blocks: Vec::from_iter((1..=blocks_amount - 1).rev()), 
+            inodes: Vec::new(),
+            free_extents: Vec::from([Extent::new(1, blocks_amount - 1)]), // block 0 holds the superblock
         }
     }
 
-    pub fn add_file(&mut self, filename: &str) {
-        if let Some(start_block) = self.available_blocks.pop() { // Removes last element 
-            let new_file = FileEntry::new(filename, start_block);
-            self.entries.push(new_file);
-        }
-        else {
-            println!("No available blocks for new file");
+    pub fn add_file(&mut self, filename: &str) -> Result<(), FsError> {
+        self.check_new_name(filename)?;
+        let start = self.allocate_extent(1, 0).ok_or(FsError::NoSpace)?;
+        let ino = self.alloc_inode(Inode {
+            kind: InodeKind::File,
+            extents: Vec::from([start]),
+            indirect: None,
+            size: 0,
+            links: 1,
+        });
+        self.entries.push(FileEntry::new(filename, ino));
+        Ok(())
+    }
+
+    /// Adds `filename` as another hard link to the inode of the entry `target`.
+    ///
+    /// Like `link(2)` on Linux, a symbolic link `target` is linked itself rather than followed.
+    pub fn add_link(&mut self, target: &str, filename: &str) -> Result<(), FsError> {
+        self.check_new_name(filename)?;
+        let index = self.lookup(target).ok_or(FsError::NotFound)?;
+        let ino = self.entries[index].inode;
+        if let Some(inode) = self.inodes[ino].as_mut() {
+            inode.links += 1;
         }
+        self.entries.push(FileEntry::new(filename, ino));
+        Ok(())
     }
-    
 
-    pub fn find_file(&self, filename: &str) -> Option<&FileEntry> {
-        for entry in &self.entries {
-            // Find the null byte index in the name
-            let name = match core::str::from_utf8(&entry.name) {
-                Ok(name_str) => name_str.trim_end_matches('\0'),  // Trim null terminators
-                Err(_) => continue, // Skip invalid UTF-8 names
-            };
-            if name == filename {
-                return Some(entry);
+    /// Adds `filename` as a symbolic link to `target`, which does not need to exist.
+    pub fn add_symlink(&mut self, target: &str, filename: &str) -> Result<(), FsError> {
+        self.check_new_name(filename)?;
+        let ino = self.alloc_inode(Inode {
+            kind: InodeKind::Symlink(String::from(target)),
+            extents: Vec::new(),
+            indirect: None,
+            size: target.len(),
+            links: 1,
+        });
+        self.entries.push(FileEntry::new(filename, ino));
+        Ok(())
+    }
+
+    /// Returns the index of the entry called `filename` without following symbolic links.
+    pub fn lookup(&self, filename: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name() == filename)
+    }
+
+    /// Resolves `filename` to an inode number, following symbolic links.
+    pub fn resolve(&self, filename: &str) -> Result<usize, FsError> {
+        let mut name = filename;
+        for _ in 0..=MAX_SYMLINK_DEPTH {
+            let index = self.lookup(name).ok_or(FsError::NotFound)?;
+            let ino = self.entries[index].inode;
+            match self.inodes[ino].as_ref().map(|inode| &inode.kind) {
+                Some(InodeKind::Symlink(target)) => name = target.as_str(),
+                _ => return Ok(ino),
             }
         }
-        None
+        Err(FsError::SymlinkLoop)
+    }
+
+    /// Returns the target of the symbolic link `filename`, or `None` if it is not a symbolic link.
+    pub fn symlink_target(&self, filename: &str) -> Option<&str> {
+        let ino = self.entries[self.lookup(filename)?].inode;
+        match &self.inodes[ino].as_ref()?.kind {
+            InodeKind::Symlink(target) => Some(target.as_str()),
+            InodeKind::File => None,
+        }
+    }
+
+    pub fn inode(&self, ino: usize) -> Result<&Inode, FsError> {
+        self.inodes.get(ino).and_then(Option::as_ref).ok_or(FsError::NotFound)
+    }
+
+    pub fn inode_mut(&mut self, ino: usize) -> Result<&mut Inode, FsError> {
+        self.inodes.get_mut(ino).and_then(Option::as_mut).ok_or(FsError::NotFound)
+    }
+
+    pub fn find_file(&self, filename: &str) -> Result<&Inode, FsError> {
+        let ino = self.resolve(filename)?;
+        self.inodes[ino].as_ref().ok_or(FsError::NotFound)
     }
 
     pub fn get_file_size(&self, filename: &str) -> usize {
         // Find the file entry in the table by the given filename
-        if let Some(file_entry) = self.find_file(filename) {
-            file_entry.size
+        if let Ok(inode) = self.find_file(filename) {
+            inode.size
         } else {
             0 
         }
     }
-    pub fn delete_file_by_name<T: BlockDevice>(&mut self, device: &mut T, file_name: &str) {
-        if let Some(index) = self.entries.iter().position(|entry| {
-            let name_str = core::str::from_utf8(&entry.name).unwrap_or("").trim_end_matches('\0');
-            name_str == file_name
-        }) {
-            // Get the blocks of the file
-            let file_entry = &self.entries[index];
-            let file_blocks = file_entry.blocks.clone(); // Clone to avoid borrowing issues
-    
-            // Overwrite all blocks with zeroes
+    pub fn delete_file_by_name<T: BlockDevice>(&mut self, device: &T, file_name: &str) {
+        if let Ok(Some(inode)) = self.find_and_remove_file(file_name) {
+            let mut file_extents = extent::load_extents(device, &inode);
+            file_extents.extend(inode.indirect.map(|block| Extent::new(block, 1)));
+
+            // Overwrite all freed blocks with zeroes
             let empty_block = [0u8; 512]; // Assuming block size is 512 bytes
-            for &block in &file_blocks {
-                device.write_block(block, &empty_block);
+            for extent in file_extents {
+                for block in extent.start..extent.end() {
+                    device.write_block(block, &empty_block);
+                }
+                self.free_extent(extent);
             }
-    
-            // Remove the file entry from the table
-            self.entries.remove(index);
-    
-            // Return freed blocks to the available list
-            self.available_blocks.extend(file_blocks);
         }
     }  
+
+    /// Takes up to `want` contiguous blocks from the free list.
+    ///
+    /// Prefers the free run starting at `goal` so a growing file stays contiguous,
+    /// then the first run large enough, and otherwise the largest run available.
+    pub fn allocate_extent(&mut self, want: usize, goal: usize) -> Option<Extent> {
+        let index = self.free_extents.iter().position(|free| free.start == goal)
+            .or_else(|| self.free_extents.iter().position(|free| free.len >= want))
+            .or_else(|| (0..self.free_extents.len()).max_by_key(|&i| self.free_extents[i].len))?;
+
+        let free = &mut self.free_extents[index];
+        let extent = Extent::new(free.start, want.min(free.len));
+        free.start += extent.len;
+        free.len -= extent.len;
+        if free.len == 0 {
+            self.free_extents.remove(index);
+        }
+        Some(extent)
+    }
+
+    /// Returns `extent` to the free list, merging it with its neighbours.
+    pub fn free_extent(&mut self, extent: Extent) {
+        let index = self.free_extents.partition_point(|free| free.start < extent.start);
+        self.free_extents.insert(index, extent);
+
+        if index + 1 < self.free_extents.len() && self.free_extents[index].end() == self.free_extents[index + 1].start {
+            self.free_extents[index].len += self.free_extents[index + 1].len;
+            self.free_extents.remove(index + 1);
+        }
+        if index > 0 && self.free_extents[index - 1].end() == self.free_extents[index].start {
+            self.free_extents[index - 1].len += self.free_extents[index].len;
+            self.free_extents.remove(index);
+        }
+    }
+
+    /// Grows or shrinks `extents` (the full extent list of inode `ino`) to cover `blocks` blocks
+    /// and stores the result in the inode.
+    ///
+    /// When the list no longer fits inline, returns the indirect block and the records
+    /// the caller has to write into it. On failure the extents are left as they were.
+    pub fn resize_inode(&mut self, ino: usize, extents: &mut Vec<Extent>, blocks: usize) -> Result<Option<(usize, Vec<Extent>)>, FsError> {
+        let old_blocks = extents.iter().map(|extent| extent.len).sum();
+        self.resize_extents(extents, blocks)?;
+
+        let indirect = self.inode(ino)?.indirect;
+        let needs_indirect = extents.len() > INLINE_EXTENTS;
+        let indirect_block = match (indirect, needs_indirect) {
+            (Some(block), true) => Some(block),
+            (None, true) => match self.allocate_extent(1, 0) {
+                Some(extent) => Some(extent.start),
+                None => {
+                    self.resize_extents(extents, old_blocks)?;
+                    return Err(FsError::NoSpace);
+                }
+            },
+            (Some(block), false) => {
+                self.free_extent(Extent::new(block, 1));
+                None
+            }
+            (None, false) => None,
+        };
+        if extents.len() > INLINE_EXTENTS + EXTENTS_PER_BLOCK {
+            if let (None, Some(block)) = (indirect, indirect_block) {
+                self.free_extent(Extent::new(block, 1));
+            }
+            self.resize_extents(extents, old_blocks)?;
+            return Err(FsError::FileTooLarge);
+        }
+
+        let inode = self.inode_mut(ino)?;
+        inode.extents = extents.iter().take(INLINE_EXTENTS).copied().collect();
+        inode.indirect = indirect_block;
+        Ok(indirect_block.map(|block| (block, extents[INLINE_EXTENTS..].to_vec())))
+    }
+
+    fn resize_extents(&mut self, extents: &mut Vec<Extent>, blocks: usize) -> Result<(), FsError> {
+        let old_blocks: usize = extents.iter().map(|extent| extent.len).sum();
+        let mut have = old_blocks;
+
+        // Shrink by releasing blocks from the end of the file
+        while have > blocks {
+            let last = extents.last_mut().expect("extents cover the blocks counted");
+            let excess = (have - blocks).min(last.len);
+            last.len -= excess;
+            let freed = Extent::new(last.end(), excess);
+            if last.len == 0 {
+                extents.pop();
+            }
+            self.free_extent(freed);
+            have -= excess;
+        }
+
+        // Grow by appending extents, extending the last one when the new blocks follow it
+        while have < blocks {
+            let goal = extents.last().map_or(0, Extent::end);
+            let Some(extent) = self.allocate_extent(blocks - have, goal) else {
+                self.resize_extents(extents, old_blocks)?;
+                return Err(FsError::NoSpace);
+            };
+            match extents.last_mut() {
+                Some(last) if last.end() == extent.start => last.len += extent.len,
+                _ => extents.push(extent),
+            }
+            have += extent.len;
+        }
+        Ok(())
+    }
     pub fn list_files<'a>(&'a self) -> Vec<&'a str> {
         self.entries
             .iter()
-            .filter_map(|entry| core::str::from_utf8(&entry.name)
-                .ok()
-                .map(|name| name.trim_end_matches('\0'))
-            )
+            .map(|entry| entry.name())
             .collect()
     }  
+
+    fn check_new_name(&self, filename: &str) -> Result<(), FsError> {
+        check_name(filename)?;
+        if self.lookup(filename).is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+        Ok(())
+    }
+
+    fn alloc_inode(&mut self, inode: Inode) -> usize {
+        // Reuse a released slot before growing the table
+        if let Some(ino) = self.inodes.iter().position(|slot| slot.is_none()) {
+            self.inodes[ino] = Some(inode);
+            ino
+        } else {
+            self.inodes.push(Some(inode));
+            self.inodes.len() - 1
+        }
+    }
+}
+
+/// Checks that `name` can name a file: there are no directories, so it may not contain '/'.
+pub fn check_name(name: &str) -> Result<(), FsError> {
+    if name.is_empty() || name.contains('/') {
+        return Err(FsError::InvalidName);
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(FsError::NameTooLong);
+    }
+    Ok(())
+}
+
+#[test_case]
+fn test_hard_link_keeps_blocks_until_last_unlink() {
+    let mut table = FileTable::new(8);
+    table.add_file("a").unwrap();
+    table.add_link("a", "b").unwrap();
+    assert!(table.find_and_remove_file("a").unwrap().is_none());
+    assert!(table.find_and_remove_file("b").unwrap().is_some());
+}
+
+#[test_case]
+fn test_symlink_loop_is_detected() {
+    let mut table = FileTable::new(8);
+    table.add_symlink("b", "a").unwrap();
+    table.add_symlink("a", "b").unwrap();
+    assert_eq!(table.resolve("a"), Err(FsError::SymlinkLoop));
+}
+
+#[test_case]
+fn test_growing_file_stays_contiguous() {
+    let mut table = FileTable::new(64);
+    table.add_file("a").unwrap();
+    let mut extents = table.find_file("a").unwrap().extents.clone();
+    assert_eq!(table.resize_inode(0, &mut extents, 10), Ok(None));
+    assert_eq!(extents, [Extent::new(1, 10)]);
+    assert_eq!(table.free_extents, [Extent::new(11, 53)]);
 }
\ No newline at end of file
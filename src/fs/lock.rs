@@ -0,0 +1,138 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+
+/// How a lock is held: many shared holders or a single exclusive one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Shared,
+    Exclusive,
+}
+
+#[derive(Default)]
+struct LockState {
+    shared: usize,       // number of shared holders
+    exclusive: bool,     // whether an exclusive holder exists
+    waiters: Vec<Waker>, // tasks to wake once the lock is released
+}
+
+impl LockState {
+    fn can_take(&self, kind: LockKind) -> bool {
+        match kind {
+            LockKind::Shared => !self.exclusive,
+            LockKind::Exclusive => !self.exclusive && self.shared == 0,
+        }
+    }
+
+    fn take(&mut self, kind: LockKind) {
+        match kind {
+            LockKind::Shared => self.shared += 1,
+            LockKind::Exclusive => self.exclusive = true,
+        }
+    }
+}
+
+/// Reader/writer locks keyed by inode number.
+///
+/// Only the inodes currently locked or waited on have an entry. Waiting is done by
+/// awaiting `lock`, which parks the task on the executor until a holder releases.
+pub struct LockTable {
+    states: Mutex<BTreeMap<usize, LockState>>,
+}
+
+impl LockTable {
+    pub const fn new() -> Self {
+        LockTable {
+            states: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Takes the lock on `ino` if that is possible without waiting.
+    pub fn try_lock(&self, ino: usize, kind: LockKind) -> Option<LockGuard<'_>> {
+        let mut states = self.states.lock();
+        let state = states.entry(ino).or_default();
+        if state.can_take(kind) {
+            state.take(kind);
+            Some(LockGuard { table: self, ino, kind })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a future that resolves once the lock on `ino` has been taken.
+    pub fn lock(&self, ino: usize, kind: LockKind) -> LockFuture<'_> {
+        LockFuture { table: self, ino, kind }
+    }
+
+    fn release(&self, ino: usize, kind: LockKind) {
+        let waiters = {
+            let mut states = self.states.lock();
+            let Some(state) = states.get_mut(&ino) else {
+                return;
+            };
+            match kind {
+                LockKind::Shared => state.shared -= 1,
+                LockKind::Exclusive => state.exclusive = false,
+            }
+            let waiters = core::mem::take(&mut state.waiters);
+            if state.shared == 0 && !state.exclusive {
+                states.remove(&ino);
+            }
+            waiters
+        };
+        // Wake outside the table lock; every waiter retries and the losers park again
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+/// A held lock, released when dropped.
+pub struct LockGuard<'a> {
+    table: &'a LockTable,
+    ino: usize,
+    kind: LockKind,
+}
+
+impl Drop for LockGuard<'_> {
+    fn drop(&mut self) {
+        self.table.release(self.ino, self.kind);
+    }
+}
+
+/// Future returned by `LockTable::lock`.
+pub struct LockFuture<'a> {
+    table: &'a LockTable,
+    ino: usize,
+    kind: LockKind,
+}
+
+impl<'a> Future for LockFuture<'a> {
+    type Output = LockGuard<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<LockGuard<'a>> {
+        let mut states = self.table.states.lock();
+        let state = states.entry(self.ino).or_default();
+        if state.can_take(self.kind) {
+            state.take(self.kind);
+            Poll::Ready(LockGuard { table: self.table, ino: self.ino, kind: self.kind })
+        } else {
+            state.waiters.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[test_case]
+fn test_shared_locks_exclude_exclusive() {
+    let table = LockTable::new();
+    let first = table.try_lock(1, LockKind::Shared);
+    let second = table.try_lock(1, LockKind::Shared);
+    assert!(first.is_some() && second.is_some());
+    assert!(table.try_lock(1, LockKind::Exclusive).is_none());
+    drop((first, second));
+    assert!(table.try_lock(1, LockKind::Exclusive).is_some());
+}
@@ -2,6 +2,12 @@
 pub mod block_device;  // Contains the BlockDevice trait and any implementations
 pub mod superblock;    // Contains the Superblock structure and related functions
 pub mod file_table;    // Contains the FileTable structure and file entry management
+pub mod extent;        // Contains the Extent record used to map file data onto blocks
 pub mod file_ops;      // Contains file operations like create, read, write, delete, etc.
 pub mod buffer;        // Contains the BlockStorage implementation (e.g., in-memory block device)
-
+pub mod error;         // Contains the FsError type returned by the filesystem operations
+pub mod lock;          // Contains the per-inode reader/writer locks used by the file operations
+pub mod open_file;     // Contains OpenFile handles and their advisory flock-style locks
+pub mod tmpfs;         // Contains TmpFs, a heap-backed filesystem for scratch data mounted at /tmp
+pub mod vfs;           // Contains the FileSystem trait and the mount table that routes paths to filesystems
+pub mod commands;      // Contains the shell commands that work on files, registered with the CLI at boot
@@ -0,0 +1,41 @@
+use crate::fs::block_device::BlockDevice;
+use crate::fs::error::FsError;
+use crate::fs::lock::{LockGuard, LockKind};
+
+/// A file opened by name, bound to the inode the name resolved to.
+///
+/// Advisory locks follow `flock(2)`: they only constrain other `OpenFile`s that also
+/// ask for a lock, and are released when the file is dropped.
+pub struct OpenFile<'a, T: BlockDevice> {
+    device: &'a T,
+    ino: usize,
+    flock: Option<LockGuard<'a>>,
+}
+
+impl<'a, T: BlockDevice> OpenFile<'a, T> {
+    pub fn open(device: &'a T, name: &str) -> Result<Self, FsError> {
+        let ino = device.get_file_table().read().resolve(name)?;
+        Ok(OpenFile { device, ino, flock: None })
+    }
+
+    /// The inode the file was opened on, the same for every link to it.
+    pub fn ino(&self) -> usize {
+        self.ino
+    }
+
+    /// Takes an advisory lock, sleeping on the executor until it is available.
+    ///
+    /// A lock already held by this file is released first, so converting between
+    /// shared and exclusive is not atomic.
+    pub async fn flock(&mut self, kind: LockKind) {
+        self.flock = None;
+        self.flock = Some(self.device.get_flocks().lock(self.ino, kind).await);
+    }
+
+    /// Takes an advisory lock without waiting, failing with `FsError::Busy` if it is held elsewhere.
+    pub fn try_flock(&mut self, kind: LockKind) -> Result<(), FsError> {
+        self.flock = None;
+        self.flock = Some(self.device.get_flocks().try_lock(self.ino, kind).ok_or(FsError::Busy)?);
+        Ok(())
+    }
+}
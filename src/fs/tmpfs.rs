@@ -0,0 +1,74 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use omega::allocator::heap_free;
+use spin::RwLock;
+use crate::fs::error::FsError;
+use crate::fs::file_table::check_name;
+use crate::fs::vfs::{FileSystem, FsFuture};
+
+/// A filesystem whose file contents live directly on the kernel heap.
+///
+/// It does not use the block device at all: files grow as needed until their
+/// contents reach the size limit given at mount time, or the heap runs out.
+pub struct TmpFs {
+    files: RwLock<BTreeMap<String, Vec<u8>>>,
+    size_limit: AtomicUsize, // bytes of file contents allowed in total
+}
+
+impl TmpFs {
+    pub const fn new() -> Self {
+        TmpFs {
+            files: RwLock::new(BTreeMap::new()),
+            size_limit: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sets how many bytes the files may hold together. Writes that would go past it fail with `NoSpace`.
+    pub fn set_size_limit(&self, bytes: usize) {
+        self.size_limit.store(bytes, Ordering::Relaxed);
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn create_file(&self, name: &str) -> Result<(), FsError> {
+        check_name(name)?; // the same names the block device accepts
+        let mut files = self.files.write();
+        if files.contains_key(name) {
+            return Err(FsError::AlreadyExists);
+        }
+        files.insert(String::from(name), Vec::new());
+        Ok(())
+    }
+
+    // Each operation holds the map for its whole length, so nothing waits across an `await`
+    fn read_file<'a>(&'a self, name: &'a str) -> FsFuture<'a, Vec<u8>> {
+        Box::pin(async move { self.files.read().get(name).cloned().ok_or(FsError::NotFound) })
+    }
+
+    fn write_file<'a>(&'a self, name: &'a str, data: &'a [u8]) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            let mut files = self.files.write();
+            let used: usize = files.values().map(Vec::len).sum();
+            let file = files.get_mut(name).ok_or(FsError::NotFound)?;
+            let growth = data.len().saturating_sub(file.len());
+            if growth > 0 && (used + growth > self.size_limit.load(Ordering::Relaxed) || heap_free() < growth) {
+                return Err(FsError::NoSpace);
+            }
+            file.clear();
+            file.extend_from_slice(data);
+            file.shrink_to_fit();
+            Ok(())
+        })
+    }
+
+    fn delete_file<'a>(&'a self, name: &'a str) -> FsFuture<'a, ()> {
+        Box::pin(async move { self.files.write().remove(name).map(|_| ()).ok_or(FsError::NotFound) })
+    }
+
+    fn list_files(&self) -> Vec<String> {
+        self.files.read().keys().cloned().collect()
+    }
+}
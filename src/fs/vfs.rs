@@ -0,0 +1,199 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use spin::RwLock;
+use crate::fs::block_device::BlockDevice;
+use crate::fs::buffer::MyBlockDevice;
+use crate::fs::error::FsError;
+use crate::fs::file_ops;
+use crate::fs::file_table::MAX_SYMLINK_DEPTH;
+use crate::fs::open_file::OpenFile;
+
+/// The future of a filesystem operation that may wait for a file, boxed so
+/// filesystems can be mounted as trait objects.
+pub type FsFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, FsError>> + 'a>>;
+
+/// The operations every mountable filesystem provides. Names are relative to the mount point.
+///
+/// Reading, writing and deleting wait for other operations on the same file to finish.
+pub trait FileSystem: Sync {
+    fn create_file(&self, name: &str) -> Result<(), FsError>;
+    fn read_file<'a>(&'a self, name: &'a str) -> FsFuture<'a, Vec<u8>>;
+    fn write_file<'a>(&'a self, name: &'a str, data: &'a [u8]) -> FsFuture<'a, ()>;
+    fn delete_file<'a>(&'a self, name: &'a str) -> FsFuture<'a, ()>;
+    fn list_files(&self) -> Vec<String>;
+
+    fn link_file(&self, _target: &str, _name: &str) -> Result<(), FsError> {
+        Err(FsError::Unsupported)
+    }
+
+    fn symlink_file(&self, _target: &str, _name: &str) -> Result<(), FsError> {
+        Err(FsError::Unsupported)
+    }
+
+    /// Returns the target of `name` if it is a symbolic link.
+    fn symlink_target(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// Opens `name` to take advisory locks on it. Only the block device keeps them.
+    fn open_file(&'static self, _name: &str) -> Result<OpenFile<'static, MyBlockDevice>, FsError> {
+        Err(FsError::Unsupported)
+    }
+}
+
+impl FileSystem for MyBlockDevice {
+    fn create_file(&self, name: &str) -> Result<(), FsError> {
+        file_ops::create_file(self, name)
+    }
+
+    fn read_file<'a>(&'a self, name: &'a str) -> FsFuture<'a, Vec<u8>> {
+        Box::pin(file_ops::read_file(self, name))
+    }
+
+    fn write_file<'a>(&'a self, name: &'a str, data: &'a [u8]) -> FsFuture<'a, ()> {
+        Box::pin(file_ops::write_file(self, name, data))
+    }
+
+    fn delete_file<'a>(&'a self, name: &'a str) -> FsFuture<'a, ()> {
+        Box::pin(file_ops::delete_file(self, name))
+    }
+
+    fn list_files(&self) -> Vec<String> {
+        self.get_file_table().read().list_files().into_iter().map(String::from).collect()
+    }
+
+    fn link_file(&self, target: &str, name: &str) -> Result<(), FsError> {
+        file_ops::link_file(self, target, name)
+    }
+
+    fn symlink_file(&self, target: &str, name: &str) -> Result<(), FsError> {
+        file_ops::symlink_file(self, target, name)
+    }
+
+    fn symlink_target(&self, name: &str) -> Option<String> {
+        self.get_file_table().read().symlink_target(name).map(String::from)
+    }
+
+    fn open_file(&'static self, name: &str) -> Result<OpenFile<'static, MyBlockDevice>, FsError> {
+        OpenFile::open(self, name)
+    }
+}
+
+struct Mount {
+    path: &'static str, // mount point without the leading '/', empty for the root
+    fs: &'static dyn FileSystem,
+}
+
+static MOUNTS: RwLock<Vec<Mount>> = RwLock::new(Vec::new());
+
+/// Mounts `fs` at the absolute path `path`, replacing whatever was mounted there.
+pub fn mount(path: &'static str, fs: &'static dyn FileSystem) {
+    let path = path.trim_matches('/');
+    let mut mounts = MOUNTS.write();
+    mounts.retain(|mount| mount.path != path);
+    mounts.push(Mount { path, fs });
+}
+
+/// Splits `path` into the filesystem mounted on its longest matching prefix and the
+/// mount point itself. Paths without a leading '/' are taken relative to the root.
+fn locate(path: &str) -> Result<(&'static dyn FileSystem, &'static str, &str), FsError> {
+    let path = path.trim_start_matches('/');
+    let mounts = MOUNTS.read();
+    mounts
+        .iter()
+        .filter_map(|mount| {
+            let rest = if mount.path.is_empty() {
+                path
+            } else if path == mount.path {
+                ""
+            } else {
+                path.strip_prefix(mount.path)?.strip_prefix('/')?
+            };
+            Some((mount.fs, mount.path, rest))
+        })
+        .max_by_key(|(_, mount_path, _)| mount_path.len())
+        .ok_or(FsError::NotFound)
+}
+
+/// Like `locate`, for a name that is about to be created, which cannot be empty.
+fn locate_new(path: &str) -> Result<(&'static dyn FileSystem, &'static str, &str), FsError> {
+    let (fs, mount_path, name) = locate(path)?;
+    if name.is_empty() {
+        return Err(FsError::InvalidName); // `path` names the mount point itself
+    }
+    Ok((fs, mount_path, name))
+}
+
+/// Like `locate`, but follows symbolic links on the final name across mount points.
+fn follow(path: &str) -> Result<(&'static dyn FileSystem, String), FsError> {
+    let mut path = path.to_string();
+    for _ in 0..=MAX_SYMLINK_DEPTH {
+        let (fs, mount_path, name) = locate(&path)?;
+        match fs.symlink_target(name) {
+            // Relative targets are resolved against the mount point of the link
+            Some(target) if target.starts_with('/') => path = target,
+            Some(target) => path = format!("/{}/{}", mount_path, target),
+            None => return Ok((fs, name.to_string())),
+        }
+    }
+    Err(FsError::SymlinkLoop)
+}
+
+pub fn create_file(path: &str) -> Result<(), FsError> {
+    let (fs, _, name) = locate_new(path)?;
+    fs.create_file(name)
+}
+
+pub async fn read_file(path: &str) -> Result<Vec<u8>, FsError> {
+    let (fs, name) = follow(path)?;
+    fs.read_file(&name).await
+}
+
+pub async fn write_file(path: &str, data: &[u8]) -> Result<(), FsError> {
+    let (fs, name) = follow(path)?;
+    fs.write_file(&name, data).await
+}
+
+pub fn open_file(path: &str) -> Result<OpenFile<'static, MyBlockDevice>, FsError> {
+    let (fs, name) = follow(path)?;
+    fs.open_file(&name)
+}
+
+/// Removes `path` itself; a symbolic link is removed, not its target.
+pub async fn delete_file(path: &str) -> Result<(), FsError> {
+    let (fs, _, name) = locate(path)?;
+    fs.delete_file(name).await
+}
+
+/// Creates `path` as a hard link to `target`, which must be on the same filesystem.
+pub fn link_file(target: &str, path: &str) -> Result<(), FsError> {
+    let (_, target_mount, target_name) = locate(target)?;
+    let (fs, mount_path, name) = locate_new(path)?;
+    if target_mount != mount_path {
+        return Err(FsError::CrossDevice);
+    }
+    fs.link_file(target_name, name)
+}
+
+pub fn symlink_file(target: &str, path: &str) -> Result<(), FsError> {
+    let (fs, _, name) = locate_new(path)?;
+    fs.symlink_file(target, name)
+}
+
+pub fn symlink_target(path: &str) -> Option<String> {
+    let (fs, _, name) = locate(path).ok()?;
+    fs.symlink_target(name)
+}
+
+/// Lists the files of the filesystem mounted at `dir`.
+pub fn list_files(dir: &str) -> Result<Vec<String>, FsError> {
+    let (fs, _, name) = locate(dir)?;
+    if !name.is_empty() {
+        return Err(FsError::NotFound); // there are no directories below a mount point
+    }
+    Ok(fs.list_files())
+}
@@ -1,10 +1,9 @@
 use crate::gdt;
-use crate::hlt_loop;
-use crate::print;
 use crate::println;
-use pc_keyboard::DecodedKey;
 use x86_64::instructions::port::Port;
-use crate::keyboard::{INPUT_BUFFER, INPUT_READY, INPUT_INDEX, KEYBOARD};
+use crate::task::keyboard::add_scancode;
+use crate::task::mouse::add_mouse_byte;
+use crate::vga_buffer::console;
 use lazy_static::lazy_static; // basically static but initallized just when called for the first time
 use x86_64::structures::idt::PageFaultErrorCode;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
@@ -28,6 +27,8 @@ lazy_static! {
 
         idt[InterruptIndex::Keyboard.as_u8()].set_handler_fn(keyboard_interrupt_handler);
 
+        idt[InterruptIndex::Mouse.as_u8()].set_handler_fn(mouse_interrupt_handler);
+
         idt.page_fault.set_handler_fn(page_fault_handler);
 
         idt
@@ -60,42 +61,26 @@ extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFr
 }
 
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    let mut keyboard = KEYBOARD.lock();
     let mut port = Port::new(KEYBOARD_PORT);
 
     let scancode: u8 = unsafe { port.read() };
+    add_scancode(console::active(), scancode); // Decoding, editing and echo happen in the reading task
 
-    if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-        if let Some(key) = keyboard.process_keyevent(key_event) {
-            unsafe {
-                match key {
-                    DecodedKey::Unicode('\n') => {
-
-                        INPUT_READY = true;
-                        println!();
-                    }
-                    DecodedKey::Unicode('\x08') => { // Backspace
-                        if INPUT_INDEX > 0 {
-                            INPUT_INDEX -= 1;
-                            print!("\x08 \x08"); // Clear character on screen
-                        }
-                    }
-                    DecodedKey::Unicode(character) => {
-                        if INPUT_INDEX < INPUT_BUFFER.len() - 1 {
-                            INPUT_BUFFER[INPUT_INDEX] = character as u8;
-                            INPUT_INDEX += 1;
-                            print!("{}", character); // Echo typed character
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        }
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
     }
+}
+
+extern "x86-interrupt" fn mouse_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    let mut port = Port::new(KEYBOARD_PORT); // the mouse shares the data port with the keyboard
+
+    let byte: u8 = unsafe { port.read() };
+    add_mouse_byte(byte); // Packets are assembled in the reading task
 
     unsafe {
         PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
+            .notify_end_of_interrupt(InterruptIndex::Mouse.as_u8());
     }
 }
 #[test_case]
@@ -120,6 +105,7 @@ pub static PICS: spin::Mutex<ChainedPics> = spin::Mutex::new(unsafe {
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    Mouse = PIC_1_OFFSET + 12, // IRQ12, on the secondary PIC
 }
 
 impl InterruptIndex {
@@ -144,5 +130,7 @@ extern "x86-interrupt" fn page_fault_handler(
     println!("Error Code: {:?}", error_code);
     println!("{:#?}", stack_frame);
 
-    hlt_loop();
+    // Keep taking keyboard interrupts so the dump can be scrolled back with Shift+PageUp
+    x86_64::instructions::interrupts::enable();
+    crate::keyboard::poll_keys_loop();
 }
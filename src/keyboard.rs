@@ -1,35 +1,431 @@
 // Importing necessary dependencies
-use pc_keyboard::{layouts, HandleControl, Keyboard, ScancodeSet1};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use core::sync::atomic::{AtomicBool, Ordering};
+use futures_util::stream::StreamExt;
+use pc_keyboard::{DecodedKey, HandleControl, KeyCode, KeyState, Keyboard, ScancodeSet1};
 use spin::Mutex;
+use x86_64::instructions::port::Port;
 use lazy_static::lazy_static; // For lazy_static! macro
+use crate::line_editor::{Completer, EditResult, History, LineEditor};
+use crate::task::keyboard::{take_scancode, ScancodeStream};
+use crate::vga_buffer::console::{self, CONSOLE_COUNT, SHELL_CONSOLE};
+use crate::vga_buffer::BUFFER_HEIGHT;
+use self::keymap::{Keymap, Layout};
 
-// Keyboard buffer and associated state
-pub static mut INPUT_BUFFER: [u8; 256] = [0; 256];
-pub static mut INPUT_INDEX: usize = 0;
-pub static mut INPUT_READY: bool = false;
+pub mod keymap; // Contains the built-in layouts and keymap files that `set_keymap` switches between
 
 // Lazy static initialization of the keyboard
 lazy_static! {
-    pub static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
-        Mutex::new(Keyboard::new(
-            ScancodeSet1::new(),
-            layouts::Us104Key,
-            HandleControl::Ignore
-        ));
+    pub static ref KEYBOARD: Mutex<Keyboard<Keymap, ScancodeSet1>> =
+        Mutex::new(new_keyboard(Keymap::new(Layout::Us)));
+}
+
+// Name of the keymap KEYBOARD decodes with, since the keyboard does not expose its layout
+static KEYMAP_NAME: Mutex<String> = Mutex::new(String::new());
+
+fn new_keyboard(keymap: Keymap) -> Keyboard<Keymap, ScancodeSet1> {
+    Keyboard::new(
+        ScancodeSet1::new(),
+        keymap,
+        HandleControl::MapLettersToUnicode // Ctrl+letter arrives as a control character for the line editor
+    )
+}
+
+/// Decodes all further key presses with `keymap`.
+///
+/// Modifier state is reset and the lock keys turned back off, so switch while no key is held.
+pub fn set_keymap(keymap: Keymap) {
+    *KEYMAP_NAME.lock() = String::from(keymap.name());
+    *KEYBOARD.lock() = new_keyboard(keymap);
+    let mut held = MODIFIERS.lock();
+    *held = HeldModifiers::new();
+    LEDS.lock().start(held.leds());
+}
+
+/// The name of the keymap in use.
+pub fn keymap_name() -> String {
+    let name = KEYMAP_NAME.lock();
+    if name.is_empty() { String::from(Layout::Us.name()) } else { name.clone() }
 }
 
 // Define keyboard port constant
 pub const KEYBOARD_PORT: u16 = 0x60;
+const KEYBOARD_STATUS_PORT: u16 = 0x64;
+const STATUS_INPUT_FULL: u8 = 0x02;
+// Status reads before a write to the keyboard is given up
+const TIMEOUT: usize = 100_000;
+
+// Replies of the keyboard to a command byte, mixed in with the scancodes
+const RESPONSE_ACK: u8 = 0xfa;
+const RESPONSE_RESEND: u8 = 0xfe;
+const COMMAND_SET_LEDS: u8 = 0xed;
+// Times a byte is sent again when the keyboard asks for it
+const LED_RETRIES: u8 = 3;
+// Scancodes that may arrive before the reply to a command before it is taken as lost
+const LED_PATIENCE: u8 = 16;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static MODIFIERS: Mutex<HeldModifiers> = Mutex::new(HeldModifiers::new());
+static LEDS: Mutex<LedCommand> = Mutex::new(LedCommand::new());
+
+// Most keys kept while the shell is not reading; older ones are dropped
+const PENDING_KEYS: usize = 64;
+// Keys typed on the shell console and decoded but not read yet
+static PENDING: Mutex<VecDeque<KeyEvent>> = Mutex::new(VecDeque::new());
+
+/// Modifier and lock state at the time a key was pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyModifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+/// A decoded key press together with the modifiers held for it.
+///
+/// Function keys and other keys without a character arrive as `DecodedKey::RawKey`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: DecodedKey,
+    pub modifiers: KeyModifiers,
+}
+
+/// The modifier keys held down and the lock keys turned on, followed from the key
+/// events since `pc-keyboard` keeps its own copy to itself.
+struct HeldModifiers {
+    lshift: bool,
+    rshift: bool,
+    lctrl: bool,
+    rctrl: bool,
+    lalt: bool,
+    ralt: bool,
+    pause: bool, // the hidden Ctrl sent ahead of Pause, which then looks like Num Lock
+    caps_lock: bool,
+    num_lock: bool,
+    scroll_lock: bool,
+}
+
+impl HeldModifiers {
+    /// The state of a keyboard that was just set up, on which Num Lock starts on.
+    const fn new() -> HeldModifiers {
+        HeldModifiers {
+            lshift: false,
+            rshift: false,
+            lctrl: false,
+            rctrl: false,
+            lalt: false,
+            ralt: false,
+            pause: false,
+            caps_lock: false,
+            num_lock: true,
+            scroll_lock: false,
+        }
+    }
+
+    /// Follows one key going down or up, returning whether a lock key was toggled.
+    fn track(&mut self, event: &pc_keyboard::KeyEvent) -> bool {
+        let down = event.state != KeyState::Up;
+        match event.code {
+            KeyCode::LShift => self.lshift = down,
+            KeyCode::RShift => self.rshift = down,
+            KeyCode::LControl => self.lctrl = down,
+            KeyCode::RControl => self.rctrl = down,
+            KeyCode::LAlt => self.lalt = down,
+            KeyCode::RAltGr => self.ralt = down,
+            KeyCode::RControl2 => self.pause = down,
+            KeyCode::CapsLock if down => {
+                self.caps_lock = !self.caps_lock;
+                return true;
+            }
+            KeyCode::NumpadLock if down && !self.pause => {
+                self.num_lock = !self.num_lock;
+                return true;
+            }
+            KeyCode::ScrollLock if down => {
+                self.scroll_lock = !self.scroll_lock;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn modifiers(&self) -> KeyModifiers {
+        KeyModifiers {
+            shift: self.lshift || self.rshift,
+            ctrl: self.lctrl || self.rctrl,
+            alt: self.lalt || self.ralt,
+            caps_lock: self.caps_lock,
+            num_lock: self.num_lock,
+            scroll_lock: self.scroll_lock,
+        }
+    }
+
+    /// The lock keys that are on, as the keyboard's LED byte.
+    fn leds(&self) -> u8 {
+        (self.scroll_lock as u8) | (self.num_lock as u8) << 1 | (self.caps_lock as u8) << 2
+    }
+}
+
+/// A Set LEDs command on its way to the keyboard, which acknowledges each of its
+/// two bytes before taking the next.
+struct LedCommand {
+    sent: Option<u8>, // the byte waiting for its ACK
+    leds: u8,
+    retries: u8,
+    waited: u8,
+    next: Option<u8>, // LEDs to light once this command is through
+}
+
+impl LedCommand {
+    const fn new() -> LedCommand {
+        LedCommand { sent: None, leds: 0, retries: 0, waited: 0, next: None }
+    }
+
+    /// Lights the LEDs in `leds`, after the command in flight if there is one.
+    fn start(&mut self, leds: u8) {
+        if self.sent.is_some() {
+            self.next = Some(leds);
+            return;
+        }
+        self.leds = leds;
+        self.send(COMMAND_SET_LEDS);
+    }
+
+    fn send(&mut self, byte: u8) {
+        self.sent = write_data(byte).map(|_| byte);
+        self.retries = 0;
+        self.waited = 0;
+    }
+
+    /// Moves the command along with a byte read from the keyboard, returning
+    /// whether the byte was a reply rather than a scancode.
+    fn receive(&mut self, byte: u8) -> bool {
+        let Some(sent) = self.sent else {
+            // A reply to a command that was given up on
+            return byte == RESPONSE_ACK || byte == RESPONSE_RESEND;
+        };
+        match byte {
+            RESPONSE_ACK if sent == COMMAND_SET_LEDS => self.send(self.leds),
+            RESPONSE_RESEND if self.retries < LED_RETRIES => {
+                let retries = self.retries + 1;
+                self.send(sent);
+                self.retries = retries;
+            }
+            RESPONSE_ACK | RESPONSE_RESEND => self.finish(),
+            _ => {
+                self.waited += 1;
+                if self.waited == LED_PATIENCE {
+                    self.finish();
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Ends the command in flight, whether it went through or not, and sends the next one.
+    fn finish(&mut self) {
+        self.sent = None;
+        if let Some(leds) = self.next.take() {
+            self.start(leds);
+        }
+    }
+}
 
-// Function to read the input from the keyboard buffer
-pub fn read_input() -> Option<&'static str> {
-    unsafe {
-        while !INPUT_READY {} // Wait until Enter is pressed
+/// Writes a byte to the keyboard, or gives up if the controller does not take it.
+fn write_data(byte: u8) -> Option<()> {
+    let mut status: Port<u8> = Port::new(KEYBOARD_STATUS_PORT);
+    (0..TIMEOUT).find(|_| unsafe { status.read() } & STATUS_INPUT_FULL == 0)?;
+    unsafe { Port::new(KEYBOARD_PORT).write(byte) };
+    Some(())
+}
+
+/// Decodes the scancodes the keyboard interrupt has queued so far, acting on
+/// Ctrl+C, Shift+PageUp/PageDown and Alt+F1/F2 and keeping the other keys for
+/// `read_key_event`.
+///
+/// Key readers and `interrupted` call this. It gives up on a lock rather than
+/// wait for it, so the panic handler can call it too.
+pub fn poll_keys() {
+    while let Some((typed_on, byte)) = take_scancode() {
+        receive_scancode(typed_on, byte);
+    }
+}
+
+/// Halts forever, waking on each interrupt to act on the keys pressed, for code
+/// that has nothing left to do but let the screen be scrolled back and switched.
+pub fn poll_keys_loop() -> ! {
+    loop {
+        x86_64::instructions::hlt();
+        poll_keys();
+    }
+}
+
+/// Handles one byte read from the keyboard while `typed_on` was the console on screen.
+fn receive_scancode(typed_on: usize, byte: u8) {
+    if LEDS.try_lock().is_some_and(|mut leds| leds.receive(byte)) {
+        return;
+    }
+    let Some(mut keyboard) = KEYBOARD.try_lock() else {
+        return;
+    };
+    let Ok(Some(key_event)) = keyboard.add_byte(byte) else {
+        return;
+    };
+    let Some(mut held) = MODIFIERS.try_lock() else {
+        return;
+    };
+    let locks_changed = held.track(&key_event);
+    let key = keyboard.process_keyevent(key_event);
+    let modifiers = held.modifiers();
+    let leds = held.leds();
+    drop((held, keyboard));
+    if locks_changed {
+        if let Some(mut command) = LEDS.try_lock() {
+            command.start(leds);
+        }
+    }
+    if let Some(key) = key {
+        handle_key(typed_on, KeyEvent { key, modifiers });
+    }
+}
+
+/// Acts on the keys that work on every console, and keeps the rest for the shell.
+fn handle_key(typed_on: usize, event: KeyEvent) {
+    match event.key {
+        DecodedKey::RawKey(code) if event.modifiers.alt => {
+            if let Some(index) = function_key(code).filter(|&index| index < CONSOLE_COUNT) {
+                console::switch_to(index);
+                return;
+            }
+        }
+        DecodedKey::RawKey(code @ (KeyCode::PageUp | KeyCode::PageDown)) if event.modifiers.shift => {
+            let page = (BUFFER_HEIGHT - 1) as isize;
+            let lines = if code == KeyCode::PageUp { page } else { -page };
+            // A panicking print may hold the writer; the key press is lost rather than deadlock
+            if let Some(mut writer) = console::console(typed_on).and_then(|writer| writer.try_lock()) {
+                writer.scroll_view(lines);
+            }
+            return;
+        }
+        // Only the shell console runs commands that Ctrl+C could stop
+        DecodedKey::Unicode('\u{3}') if typed_on == SHELL_CONSOLE => INTERRUPTED.store(true, Ordering::Relaxed),
+        _ => {}
+    }
+    // The log console only shows messages, so keys typed on it are dropped
+    if typed_on == SHELL_CONSOLE {
+        keep(event);
+    }
+}
+
+/// The position of F1 to F12 among the function keys.
+fn function_key(code: KeyCode) -> Option<usize> {
+    const KEYS: [KeyCode; 12] = [
+        KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+        KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    ];
+    KEYS.iter().position(|&key| key == code)
+}
+
+/// Whether Ctrl+C was pressed since the last `clear_interrupt`.
+///
+/// Long-running commands and script loops poll this and stop early.
+pub fn interrupted() -> bool {
+    poll_keys();
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+pub fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::Relaxed);
+}
+
+/// Waits until Ctrl+C is pressed, acting on the other keys meanwhile.
+///
+/// A command races this against a wait that might never end. Like
+/// `read_key_event`, it must not run alongside another key reader.
+pub async fn wait_for_interrupt() {
+    let mut scancodes = ScancodeStream::new();
+    while !interrupted() {
+        let (typed_on, byte) = scancodes.next().await.expect("the scancode stream never ends");
+        receive_scancode(typed_on, byte);
+    }
+}
 
-        let input_str = core::str::from_utf8(&INPUT_BUFFER[..INPUT_INDEX]).ok()?;
+/// Throws away the keys typed on the shell console so far, such as the Ctrl+C that
+/// interrupted a command.
+pub fn discard_input() {
+    poll_keys();
+    PENDING.lock().clear();
+}
 
-        INPUT_INDEX = 0; // Reset index only after reading the input
-        INPUT_READY = false;
-        Some(input_str)
+fn keep(event: KeyEvent) {
+    let Some(mut pending) = PENDING.try_lock() else {
+        return;
+    };
+    if pending.len() == PENDING_KEYS {
+        pending.pop_front();
     }
+    pending.push_back(event);
+}
+
+/// Waits for the next key press, sleeping until the keyboard interrupt delivers a scancode.
+pub async fn read_key() -> DecodedKey {
+    read_key_event().await.key
+}
+
+/// Waits for the next key press on the shell console like `read_key`, and also
+/// returns the modifiers held for it.
+///
+/// Like `ScancodeStream`, only one reader may wait at a time.
+pub async fn read_key_event() -> KeyEvent {
+    let mut scancodes = ScancodeStream::new();
+    loop {
+        poll_keys();
+        if let Some(event) = PENDING.lock().pop_front() {
+            return event;
+        }
+        let (typed_on, byte) = scancodes.next().await.expect("the scancode stream never ends");
+        receive_scancode(typed_on, byte);
+    }
+}
+
+// Function to read a line of input, edited in place until Enter is pressed.
+// Returns None when Ctrl+D is pressed on an empty line or Ctrl+C cancels the line.
+pub async fn read_input() -> Option<String> {
+    read_line("", &History::new(), &()).await
+}
+
+/// Prints `prompt` and reads a line like `read_input`, letting Up/Down and Ctrl+R
+/// recall entries from `history` and Tab complete words through `completer`.
+pub async fn read_line(prompt: &str, history: &History, completer: &dyn Completer) -> Option<String> {
+    let mut editor = LineEditor::new(prompt);
+    loop {
+        match editor.handle_key(read_key().await, history, completer) {
+            EditResult::Continue => {}
+            EditResult::Done => return Some(editor.line()),
+            EditResult::Cancelled | EditResult::EndOfInput => return None,
+        }
+    }
+}
+
+#[test_case]
+fn test_track_modifiers() {
+    use pc_keyboard::KeyEvent as RawKeyEvent;
+    let mut held = HeldModifiers::new();
+    assert!(!held.track(&RawKeyEvent::new(KeyCode::LShift, KeyState::Down)));
+    assert!(held.track(&RawKeyEvent::new(KeyCode::CapsLock, KeyState::Down)));
+    assert!(!held.track(&RawKeyEvent::new(KeyCode::CapsLock, KeyState::Up)));
+    // Pause sends a hidden Ctrl ahead of its Num Lock code
+    held.track(&RawKeyEvent::new(KeyCode::RControl2, KeyState::Down));
+    assert!(!held.track(&RawKeyEvent::new(KeyCode::NumpadLock, KeyState::Down)));
+    let modifiers = held.modifiers();
+    assert!(modifiers.shift && modifiers.caps_lock && modifiers.num_lock && !modifiers.ctrl);
+    assert_eq!(held.leds(), 0b110);
+    held.track(&RawKeyEvent::new(KeyCode::LShift, KeyState::Up));
+    assert!(!held.modifiers().shift);
 }
@@ -0,0 +1,182 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use pc_keyboard::{layouts, DecodedKey, HandleControl, KeyCode, KeyboardLayout, Modifiers};
+
+/// The built-in layouts of `pc-keyboard`, by the name `keymap` knows them under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Us,
+    Uk,
+    De,
+    Azerty,
+    Dvorak,
+    Colemak,
+    Jis,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 7] = [
+        Layout::Us,
+        Layout::Uk,
+        Layout::De,
+        Layout::Azerty,
+        Layout::Dvorak,
+        Layout::Colemak,
+        Layout::Jis,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Us => "us",
+            Layout::Uk => "uk",
+            Layout::De => "de",
+            Layout::Azerty => "fr",
+            Layout::Dvorak => "dvorak",
+            Layout::Colemak => "colemak",
+            Layout::Jis => "jis",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Layout> {
+        Layout::ALL.into_iter().find(|layout| layout.name() == name)
+    }
+}
+
+impl KeyboardLayout for Layout {
+    fn map_keycode(&self, keycode: KeyCode, modifiers: &Modifiers, handle_ctrl: HandleControl) -> DecodedKey {
+        match self {
+            Layout::Us => layouts::Us104Key.map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::Uk => layouts::Uk105Key.map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::De => layouts::De105Key.map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::Azerty => layouts::Azerty.map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::Dvorak => layouts::Dvorak104Key.map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::Colemak => layouts::Colemak.map_keycode(keycode, modifiers, handle_ctrl),
+            Layout::Jis => layouts::Jis109Key.map_keycode(keycode, modifiers, handle_ctrl),
+        }
+    }
+}
+
+// Keys that produce characters and can be remapped by a keymap file
+const REMAPPABLE_KEYS: [KeyCode; 50] = [
+    KeyCode::Oem8, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5,
+    KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9, KeyCode::Key0, KeyCode::OemMinus,
+    KeyCode::OemPlus, KeyCode::Q, KeyCode::W, KeyCode::E, KeyCode::R, KeyCode::T, KeyCode::Y,
+    KeyCode::U, KeyCode::I, KeyCode::O, KeyCode::P, KeyCode::Oem4, KeyCode::Oem6, KeyCode::Oem5,
+    KeyCode::A, KeyCode::S, KeyCode::D, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::J, KeyCode::K,
+    KeyCode::L, KeyCode::Oem1, KeyCode::Oem3, KeyCode::Oem7, KeyCode::Z, KeyCode::X, KeyCode::C,
+    KeyCode::V, KeyCode::B, KeyCode::N, KeyCode::M, KeyCode::OemComma, KeyCode::OemPeriod,
+    KeyCode::Oem2, KeyCode::Oem9, KeyCode::Oem10,
+];
+
+/// A key the keymap file assigns its own characters to.
+#[derive(Debug, Clone)]
+struct Override {
+    key: KeyCode,
+    normal: char,
+    shifted: char,
+}
+
+/// A built-in layout with some keys remapped, as loaded from a keymap file.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    name: String,
+    layout: Layout,
+    overrides: Vec<Override>,
+}
+
+/// Errors in a keymap file, with the line they were found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeymapError {
+    UnknownLayout(usize),
+    UnknownKey(usize),
+    BadCharacter(usize),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeymapError::UnknownLayout(line) => write!(f, "line {}: unknown base layout", line),
+            KeymapError::UnknownKey(line) => write!(f, "line {}: unknown key name", line),
+            KeymapError::BadCharacter(line) => write!(f, "line {}: expected one character per level", line),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn new(layout: Layout) -> Self {
+        Keymap { name: String::from(layout.name()), layout, overrides: Vec::new() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Parses a keymap file.
+    ///
+    /// ```text
+    /// # Comments start with '#'
+    /// base de            the layout the other lines change, `us` if missing
+    /// Oem5 < >           a key name, its character, and optionally its shifted character
+    /// ```
+    ///
+    /// Key names are those of `pc_keyboard::KeyCode`; only keys that type characters
+    /// can be remapped. The extra key left of Z on ISO keyboards is `Oem5`.
+    pub fn parse(name: &str, text: &str) -> Result<Keymap, KeymapError> {
+        let mut keymap = Keymap::new(Layout::Us);
+        keymap.name = String::from(name);
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                [comment, ..] if comment.starts_with('#') => {}
+                ["base", layout] => keymap.layout = Layout::from_name(layout).ok_or(KeymapError::UnknownLayout(number))?,
+                [key, characters @ ..] if characters.len() <= 2 => {
+                    let key = REMAPPABLE_KEYS
+                        .into_iter()
+                        .find(|code| alloc::format!("{:?}", code) == *key)
+                        .ok_or(KeymapError::UnknownKey(number))?;
+                    let single = |word: &str| {
+                        let mut chars = word.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(character), None) => Ok(character),
+                            _ => Err(KeymapError::BadCharacter(number)),
+                        }
+                    };
+                    let normal = single(characters.first().ok_or(KeymapError::BadCharacter(number))?)?;
+                    let shifted = match characters.get(1) {
+                        Some(word) => single(word)?,
+                        None => normal.to_uppercase().next().unwrap_or(normal),
+                    };
+                    keymap.overrides.retain(|other| other.key != key);
+                    keymap.overrides.push(Override { key, normal, shifted });
+                }
+                _ => return Err(KeymapError::BadCharacter(number)),
+            }
+        }
+        Ok(keymap)
+    }
+}
+
+impl KeyboardLayout for Keymap {
+    fn map_keycode(&self, keycode: KeyCode, modifiers: &Modifiers, handle_ctrl: HandleControl) -> DecodedKey {
+        let Some(remapped) = self.overrides.iter().find(|remapped| remapped.key == keycode) else {
+            return self.layout.map_keycode(keycode, modifiers, handle_ctrl);
+        };
+        // Caps Lock only affects letters, like in the built-in layouts
+        let shifted = if remapped.normal.is_alphabetic() { modifiers.is_caps() } else { modifiers.is_shifted() };
+        DecodedKey::Unicode(if shifted { remapped.shifted } else { remapped.normal })
+    }
+}
+
+#[test_case]
+fn test_parse_keymap_file() {
+    let keymap = Keymap::parse("custom", "# pipe on the extra key\nbase de\nOem5 | >\nQ q\n").unwrap();
+    assert_eq!(keymap.layout, Layout::De);
+    assert_eq!(keymap.overrides.len(), 2);
+    assert_eq!(keymap.overrides[0].shifted, '>');
+    assert_eq!(keymap.overrides[1].shifted, 'Q');
+    assert_eq!(Keymap::parse("bad", "base xx").unwrap_err(), KeymapError::UnknownLayout(1));
+    assert_eq!(Keymap::parse("bad", "Nope a").unwrap_err(), KeymapError::UnknownKey(1));
+}
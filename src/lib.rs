@@ -14,6 +14,9 @@ pub mod memory;
 pub mod allocator;
 pub mod task;
 pub mod keyboard;
+pub mod mouse;
+pub mod line_editor;
+pub mod framebuffer;
 
 
 
@@ -75,15 +78,6 @@ pub fn test_panic_handler(info: &PanicInfo) -> ! {
     hlt_loop(); // Halt the CPU to prevent further execution
 }
 
-/// Entry point for `cargo test`, called when running tests in QEMU
-#[cfg(test)]
-#[no_mangle] // Prevents name mangling to ensure this is recognized as `_start`
-pub extern "C" fn _start() -> ! {
-    init(); // Initialize the system (GDT, IDT, PICs, etc.)
-    test_main(); // Run all tests
-    hlt_loop(); // Halt the CPU after tests complete
-}
-
 #[cfg(test)]
 use bootloader::{entry_point, BootInfo};
 
@@ -92,8 +86,13 @@ entry_point!(test_kernel_main);
 
 /// Entry point for `cargo test`
 #[cfg(test)]
-fn test_kernel_main(_boot_info: &'static BootInfo) -> ! {
+fn test_kernel_main(boot_info: &'static BootInfo) -> ! {
     init();
+    // Some unit tests allocate, so they need the heap like the kernel does
+    let phys_mem_offset = x86_64::VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { memory::BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
     test_main();
     hlt_loop();
 }
@@ -110,6 +109,7 @@ pub fn init() {
     gdt::init(); // Initialize the Global Descriptor Table (GDT)
     interrupts::init_idt(); // Initialize the Interrupt Descriptor Table (IDT)
     unsafe { interrupts::PICS.lock().initialize() }; // Initialize the Programmable Interrupt Controllers (PICs)
+    mouse::init(); // Enable the PS/2 mouse and unmask its interrupt, if there is one
     x86_64::instructions::interrupts::enable(); // Enable hardware interrupts
 }
 
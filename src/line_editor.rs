@@ -0,0 +1,418 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use pc_keyboard::{DecodedKey, KeyCode};
+use x86_64::instructions::interrupts;
+use crate::vga_buffer::{BUFFER_WIDTH, WRITER};
+
+/// Longest line the editor accepts.
+const MAX_LINE: usize = 255;
+/// Number of lines `History` keeps before dropping the oldest.
+pub const HISTORY_SIZE: usize = 100;
+
+/// What the caller should do after a key has been handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditResult {
+    Continue,   // keep feeding keys
+    Done,       // Enter was pressed, the line is complete
+    Cancelled,  // Ctrl+C threw the line away
+    EndOfInput, // Ctrl+D was pressed on an empty line
+}
+
+/// A bounded ring of previously entered lines, oldest first.
+///
+/// Entries are numbered from 1 in the order they were added, and keep their
+/// number when older entries fall out of the ring.
+#[derive(Default)]
+pub struct History {
+    entries: VecDeque<String>,
+    added: usize, // number of entries ever added
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            entries: VecDeque::new(),
+            added: 0,
+        }
+    }
+
+    /// Appends `line` unless it is blank or repeats the newest entry.
+    pub fn push(&mut self, line: &str) {
+        if line.trim().is_empty() || self.entries.back().map(String::as_str) == Some(line) {
+            return;
+        }
+        if self.entries.len() == HISTORY_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(String::from(line));
+        self.added += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entry with the given number, as shown by `iter`.
+    pub fn get(&self, number: usize) -> Option<&str> {
+        let first = self.added - self.entries.len() + 1;
+        let index = number.checked_sub(first)?;
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.entries.back().map(String::as_str)
+    }
+
+    /// Iterates over `(number, line)` pairs from the oldest entry to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        let first = self.added - self.entries.len() + 1;
+        self.entries.iter().enumerate().map(move |(i, line)| (first + i, line.as_str()))
+    }
+
+    /// Returns the index of the newest entry at or before `from` containing `query`.
+    fn search(&self, query: &str, from: usize) -> Option<usize> {
+        (0..=from.min(self.entries.len().checked_sub(1)?))
+            .rev()
+            .find(|&index| self.entries[index].contains(query))
+    }
+}
+
+/// Supplies Tab completions to the line editor.
+pub trait Completer {
+    /// Returns every word that could replace `word`, the word in front of the cursor.
+    /// `before` is the text of the line preceding that word.
+    fn complete(&self, before: &str, word: &str) -> Vec<String>;
+}
+
+/// Completes nothing, for input that is not a command line.
+impl Completer for () {
+    fn complete(&self, _before: &str, _word: &str) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// State of a Ctrl+R reverse incremental search.
+struct Search {
+    query: String,
+    found: Option<usize>, // index of the matching history entry
+    original: Vec<char>,  // line to restore when the search is cancelled
+}
+
+/// Edits a single input line on the bottom row of the VGA console.
+///
+/// The line starts right after the prompt, which the editor prints itself so it
+/// can redraw it after listing completions. Lines wider than the rest of the row scroll
+/// horizontally so the cursor always stays visible.
+pub struct LineEditor<'a> {
+    prompt: &'a str,
+    line: Vec<char>,
+    cursor: usize,                 // index into `line` where the next character is inserted
+    start_col: usize,              // screen column of the first visible character
+    scroll: usize,                 // index of the first visible character
+    recalled: Option<usize>,       // history entry shown by Up/Down, if any
+    typed: Vec<char>,              // the line being typed before Up was first pressed
+    search: Option<Search>,
+    after_tab: bool,               // whether the previous key was Tab
+}
+
+impl<'a> LineEditor<'a> {
+    /// Prints `prompt` and starts editing an empty line after it.
+    pub fn new(prompt: &'a str) -> Self {
+        crate::print!("{}", prompt);
+        let start_col = interrupts::without_interrupts(|| WRITER.lock().column());
+        LineEditor {
+            prompt,
+            line: Vec::new(),
+            cursor: 0,
+            start_col,
+            scroll: 0,
+            recalled: None,
+            typed: Vec::new(),
+            search: None,
+            after_tab: false,
+        }
+    }
+
+    /// Applies one decoded key to the line and redraws it.
+    pub fn handle_key(&mut self, key: DecodedKey, history: &History, completer: &dyn Completer) -> EditResult {
+        let repeated_tab = core::mem::replace(&mut self.after_tab, key == DecodedKey::Unicode('\t'));
+        if self.search.is_some() && !self.handle_search_key(key, history) {
+            self.render();
+            return EditResult::Continue;
+        }
+        match key {
+            DecodedKey::Unicode('\n') => {
+                self.cursor = self.line.len();
+                self.render();
+                crate::println!();
+                return EditResult::Done;
+            }
+            DecodedKey::Unicode('\x03') => { // Ctrl+C
+                crate::println!("^C");
+                return EditResult::Cancelled;
+            }
+            DecodedKey::Unicode('\x04') if self.line.is_empty() => { // Ctrl+D
+                crate::println!();
+                return EditResult::EndOfInput;
+            }
+            DecodedKey::Unicode('\x04') => self.delete_at_cursor(),
+            DecodedKey::Unicode('\x0c') => { // Ctrl+L
+                interrupts::without_interrupts(|| WRITER.lock().clear_screen());
+                self.redraw_prompt();
+            }
+            DecodedKey::Unicode('\x08') => self.delete_before_cursor(), // Backspace
+            DecodedKey::Unicode('\x7f') | DecodedKey::RawKey(KeyCode::Delete) => self.delete_at_cursor(),
+            DecodedKey::Unicode('\x01') | DecodedKey::RawKey(KeyCode::Home) => self.cursor = 0, // Ctrl+A
+            DecodedKey::Unicode('\x05') | DecodedKey::RawKey(KeyCode::End) => self.cursor = self.line.len(), // Ctrl+E
+            DecodedKey::Unicode('\x0b') => self.line.truncate(self.cursor), // Ctrl+K
+            DecodedKey::Unicode('\x15') => { // Ctrl+U
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            DecodedKey::Unicode('\x17') => self.delete_word_before_cursor(), // Ctrl+W
+            DecodedKey::Unicode('\t') => self.complete(completer, repeated_tab),
+            DecodedKey::Unicode('\x12') => { // Ctrl+R
+                self.search = Some(Search {
+                    query: String::new(),
+                    found: None,
+                    original: self.line.clone(),
+                });
+            }
+            DecodedKey::RawKey(KeyCode::ArrowLeft) => self.cursor = self.cursor.saturating_sub(1),
+            DecodedKey::RawKey(KeyCode::ArrowRight) => self.cursor = (self.cursor + 1).min(self.line.len()),
+            DecodedKey::RawKey(KeyCode::ArrowUp) => self.recall_older(history),
+            DecodedKey::RawKey(KeyCode::ArrowDown) => self.recall_newer(history),
+            DecodedKey::Unicode(character) if !character.is_control() => self.insert(character),
+            _ => return EditResult::Continue, // other control keys are ignored
+        }
+        self.render();
+        EditResult::Continue
+    }
+
+    /// Returns the line edited so far.
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    fn set_line(&mut self, line: &[char]) {
+        self.line = Vec::from(line);
+        self.cursor = self.line.len();
+    }
+
+    fn recall_older(&mut self, history: &History) {
+        let index = match self.recalled {
+            None if history.is_empty() => return,
+            None => {
+                self.typed = self.line.clone();
+                history.len() - 1
+            }
+            Some(index) => index.saturating_sub(1),
+        };
+        self.recalled = Some(index);
+        let entry: Vec<char> = history.entries[index].chars().collect();
+        self.set_line(&entry);
+    }
+
+    fn recall_newer(&mut self, history: &History) {
+        match self.recalled {
+            Some(index) if index + 1 < history.len() => {
+                self.recalled = Some(index + 1);
+                let entry: Vec<char> = history.entries[index + 1].chars().collect();
+                self.set_line(&entry);
+            }
+            Some(_) => {
+                // Moving past the newest entry brings back what was being typed
+                self.recalled = None;
+                let typed = core::mem::take(&mut self.typed);
+                self.set_line(&typed);
+            }
+            None => {}
+        }
+    }
+
+    /// Handles a key while searching. Returns `true` when the search ended and the key
+    /// still has to be processed as a normal edit.
+    fn handle_search_key(&mut self, key: DecodedKey, history: &History) -> bool {
+        let Some(search) = self.search.as_mut() else {
+            return true;
+        };
+        match key {
+            DecodedKey::Unicode('\x12') => { // Ctrl+R again: next older match
+                if let Some(older) = search.found.and_then(|found| found.checked_sub(1)) {
+                    search.found = history.search(&search.query, older).or(search.found);
+                }
+            }
+            DecodedKey::Unicode('\x08') => {
+                search.query.pop();
+                search.found = history.search(&search.query, usize::MAX);
+            }
+            DecodedKey::Unicode('\x1b') | DecodedKey::Unicode('\x07') => { // Esc or Ctrl+G cancels
+                let original = core::mem::take(&mut search.original);
+                self.search = None;
+                self.set_line(&original);
+                return false;
+            }
+            DecodedKey::Unicode(character) if !character.is_control() => {
+                search.query.push(character);
+                let from = search.found.unwrap_or(usize::MAX);
+                search.found = history.search(&search.query, from);
+            }
+            _ => {
+                // Any other key accepts the match and is then handled normally
+                if search.found.is_none() {
+                    let original = core::mem::take(&mut search.original);
+                    self.set_line(&original);
+                }
+                self.search = None;
+                return true;
+            }
+        }
+        // Keep the matched entry in the line so accepting it needs no further lookup
+        if let Some(index) = self.search.as_ref().and_then(|search| search.found) {
+            let entry: Vec<char> = history.entries[index].chars().collect();
+            self.set_line(&entry);
+        }
+        false
+    }
+
+    /// Completes the word in front of the cursor, or lists the candidates on a second Tab.
+    fn complete(&mut self, completer: &dyn Completer, list: bool) {
+        let start = self.line[..self.cursor].iter().rposition(|&c| c == ' ').map_or(0, |space| space + 1);
+        let before: String = self.line[..start].iter().collect();
+        let word: String = self.line[start..self.cursor].iter().collect();
+        let candidates = completer.complete(&before, &word);
+
+        let completion = match candidates.as_slice() {
+            [] => return,
+            [only] if only.ends_with('/') => only.clone(),
+            [only] => alloc::format!("{} ", only),
+            [first, rest @ ..] => {
+                // Extend the word as far as all candidates agree
+                let mut common = first.len();
+                for candidate in rest {
+                    common = first.bytes().zip(candidate.bytes()).take(common).take_while(|(a, b)| a == b).count();
+                }
+                while !first.is_char_boundary(common) {
+                    common -= 1;
+                }
+                if common <= word.len() && list {
+                    self.list_candidates(&candidates);
+                }
+                String::from(&first[..common.max(word.len())])
+            }
+        };
+        self.line.drain(start..self.cursor);
+        for (offset, character) in completion.chars().enumerate() {
+            self.line.insert(start + offset, character);
+        }
+        self.cursor = start + completion.chars().count();
+        self.line.truncate(MAX_LINE);
+        self.cursor = self.cursor.min(self.line.len());
+    }
+
+    /// Prints `candidates` below the line and redraws the prompt underneath them.
+    fn list_candidates(&mut self, candidates: &[String]) {
+        crate::println!();
+        for candidate in candidates {
+            crate::print!("{}  ", candidate);
+        }
+        crate::println!();
+        self.redraw_prompt();
+    }
+
+    /// Prints the prompt again at the writer's position; the line follows on the next render.
+    fn redraw_prompt(&mut self) {
+        crate::print!("{}", self.prompt);
+        self.start_col = interrupts::without_interrupts(|| WRITER.lock().column());
+        self.scroll = 0;
+    }
+
+    fn insert(&mut self, character: char) {
+        if self.line.len() < MAX_LINE {
+            self.line.insert(self.cursor, character);
+            self.cursor += 1;
+        }
+    }
+
+    fn delete_before_cursor(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.line.remove(self.cursor);
+        }
+    }
+
+    fn delete_at_cursor(&mut self) {
+        if self.cursor < self.line.len() {
+            self.line.remove(self.cursor);
+        }
+    }
+
+    /// Deletes the whitespace and then the word in front of the cursor, like Ctrl+W in a Unix shell.
+    fn delete_word_before_cursor(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.line[start - 1] == ' ' {
+            start -= 1;
+        }
+        while start > 0 && self.line[start - 1] != ' ' {
+            start -= 1;
+        }
+        self.line.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    /// Redraws the visible part of the line and leaves the writer at the cursor.
+    fn render(&mut self) {
+        // While searching, the line area shows the query and the entry it matched
+        let search_display: Option<Vec<char>> = self.search.as_ref().map(|search| {
+            let found = search.found.map(|_| "").unwrap_or("failing ");
+            let mut display: Vec<char> = alloc::format!("({}reverse-i-search)`{}': ", found, search.query).chars().collect();
+            match search.found {
+                Some(_) => display.extend(&self.line),
+                None => display.extend(&search.original),
+            }
+            display
+        });
+        let (text, cursor) = match &search_display {
+            Some(display) => (display.as_slice(), display.len()),
+            None => (self.line.as_slice(), self.cursor),
+        };
+
+        // The last column stays free so the writer never wraps onto a new row
+        let width = BUFFER_WIDTH.saturating_sub(self.start_col + 1).max(1);
+        if cursor < self.scroll {
+            self.scroll = cursor;
+        } else if cursor > self.scroll + width {
+            self.scroll = cursor - width;
+        }
+        let scroll = self.scroll.min(text.len());
+
+        interrupts::without_interrupts(|| {
+            let mut writer = WRITER.lock();
+            writer.set_column(self.start_col);
+            for character in text[scroll..].iter().take(width) {
+                let mut utf8 = [0u8; 4];
+                writer.write_string(character.encode_utf8(&mut utf8));
+            }
+            writer.clear_to_end_of_line();
+            writer.set_column(self.start_col + cursor - scroll);
+        });
+    }
+}
+
+#[test_case]
+fn test_history_keeps_numbers_when_full() {
+    let mut history = History::new();
+    for i in 0..HISTORY_SIZE + 5 {
+        history.push(&alloc::format!("echo {}", i));
+    }
+    history.push("echo 104"); // repeating the newest entry is ignored
+    assert_eq!(history.len(), HISTORY_SIZE);
+    assert_eq!(history.get(5), None);
+    assert_eq!(history.get(6), Some("echo 5"));
+    assert_eq!(history.last(), Some("echo 104"));
+}
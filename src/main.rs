@@ -5,14 +5,20 @@
 #![reexport_test_harness_main = "test_main"]
 use spin::Mutex;
 extern crate alloc;
+use alloc::boxed::Box;
 mod fs;
 mod cli;
 use core::panic::PanicInfo;
 use fs::file_table::FileTable;
-use omega::println;
+use omega::{log, println};
 use bootloader::{BootInfo, entry_point};
 use crate::fs::buffer::MyBlockDevice;
-use crate::cli::cli_loop;
+use crate::fs::tmpfs::TmpFs;
+use crate::cli::{cli_loop, source_file, Shell};
+use crate::cli::io::Io;
+use omega::keyboard::keymap::{Keymap, Layout};
+use omega::keyboard::set_keymap;
+use omega::task::{executor::Executor, Task};
 
 use fs::file_ops::format_fs;
 static mut STORAGE: [u8; 512 * 1024] = [0; 512 * 1024]; // 512KB storage
@@ -20,7 +26,12 @@ static mut STORAGE: [u8; 512 * 1024] = [0; 512 * 1024]; // 512KB storage
 entry_point!(kernel_main);
 
 
-static DEVICE: Mutex<Option<MyBlockDevice>> = Mutex::new(None); // Use Mutex to make it mutable and safe
+static DEVICE: Mutex<Option<&'static MyBlockDevice>> = Mutex::new(None); // Use Mutex to make it mutable and safe
+static TMPFS: TmpFs = TmpFs::new(); // Scratch files on the heap, mounted at /tmp
+
+// Startup script installed on the freshly formatted filesystem and run before the first prompt
+const RC_PATH: &str = "/etc/rc";
+const DEFAULT_RC: &str = include_str!("../etc/rc");
 
 
 fn kernel_main(boot_info: &'static BootInfo) -> ! {
@@ -35,22 +46,69 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
     // Initialize the allocator
     allocator::init_heap(&mut mapper, &mut frame_allocator)
         .expect("heap initialization failed");
-    let mut device = unsafe { MyBlockDevice::new(&mut STORAGE) };
+    omega::vga_buffer::console::init(); // Adds the log console and keeps the lines that scroll off the screen
+    log!("OmegaOS kernel log; Alt+F1 returns to the shell");
+    match (omega::mouse::is_present(), omega::mouse::packet_size()) {
+        (false, _) => log!("No PS/2 mouse found"),
+        (true, 4) => log!("PS/2 mouse with wheel enabled"),
+        (true, _) => log!("PS/2 mouse enabled"),
+    }
+    // Maps the video memory the gfx command draws into, if there is a Bochs/QEMU display
+    match omega::framebuffer::init(&mut mapper, &mut frame_allocator) {
+        Ok(()) => log!("Framebuffer mapped at {:#x}", omega::framebuffer::FRAMEBUFFER_START),
+        Err(e) => log!("No framebuffer: {}", e),
+    }
+    // Leaked so open files can keep borrowing it after the lock below is released
+    let device: &'static MyBlockDevice = Box::leak(Box::new(unsafe { MyBlockDevice::new(&mut STORAGE) }));
     // Format the filesystem
-    format_fs(&mut device);
+    format_fs(device);
     // Lock the DEVICE mutex and set it
     let mut device_lock = DEVICE.lock();
     *device_lock = Some(device); // Initialize the global device
 
     drop(device_lock);  // drop the lock to allow other parts to acquire it
-
-    cli_loop();
+    fs::vfs::mount("/", device);
+    // /tmp may take half of the heap that is still free once the kernel is up
+    TMPFS.set_size_limit(omega::allocator::heap_free() / 2);
+    fs::vfs::mount("/tmp", &TMPFS);
+    log!("Mounted / and /tmp");
+    cli::register_commands();
+    fs::commands::register_commands();
+    // Build with OMEGA_KEYMAP=<layout> to start with a layout other than US
+    if let Some(name) = option_env!("OMEGA_KEYMAP") {
+        match Layout::from_name(name) {
+            Some(layout) => set_keymap(Keymap::new(layout)),
+            None => println!("Unknown keymap {}; keeping us", name),
+        }
+    }
 
     // Run tests if in test mode
     #[cfg(test)]
     test_main();
 
-    omega::hlt_loop()
+    // The shell runs as a task so the CPU halts while it waits for keys
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(shell_main()));
+    if omega::mouse::is_present() {
+        executor.spawn(Task::new(omega::task::mouse::scroll_with_wheel()));
+    }
+    executor.run()
+}
+
+/// Runs the startup script, then the interactive shell.
+async fn shell_main() {
+    let mut shell = Shell::new();
+    let installed = match fs::vfs::create_file(RC_PATH) {
+        Ok(()) => fs::vfs::write_file(RC_PATH, DEFAULT_RC.as_bytes()).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = installed {
+        println!("Could not install {}: {}", RC_PATH, e);
+    }
+    if let Err(e) = source_file(&mut shell, &mut Io::console(), RC_PATH).await {
+        println!("{}: {}", RC_PATH, e);
+    }
+    cli_loop(&mut shell).await;
 }
 
 
@@ -60,7 +118,9 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     println!("{}", info);
-    omega::hlt_loop();
+    // Keep taking keyboard interrupts so the message can be scrolled back with Shift+PageUp
+    x86_64::instructions::interrupts::enable();
+    omega::keyboard::poll_keys_loop();
 }
 
 #[cfg(test)]
@@ -0,0 +1,194 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use x86_64::instructions::port::Port;
+
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64; // reads the status, writes controller commands
+const PIC_1_DATA_PORT: u16 = 0x21;
+const PIC_2_DATA_PORT: u16 = 0xa1;
+
+// Controller commands
+const ENABLE_AUX: u8 = 0xa8;
+const READ_CONFIG: u8 = 0x20;
+const WRITE_CONFIG: u8 = 0x60;
+const WRITE_AUX: u8 = 0xd4; // the next data byte goes to the mouse
+
+// Mouse commands
+const SET_DEFAULTS: u8 = 0xf6;
+const SET_SAMPLE_RATE: u8 = 0xf3;
+const GET_DEVICE_ID: u8 = 0xf2;
+const ENABLE_REPORTING: u8 = 0xf4;
+const ACK: u8 = 0xfa;
+
+const STATUS_OUTPUT_FULL: u8 = 0x01;
+const STATUS_INPUT_FULL: u8 = 0x02;
+const CONFIG_AUX_INTERRUPT: u8 = 0x02;
+const CONFIG_AUX_CLOCK_DISABLED: u8 = 0x20;
+
+// Device ID reported once the wheel has been unlocked
+const ID_WHEEL_MOUSE: u8 = 3;
+// Status reads before giving up on a controller or mouse that does not answer
+const TIMEOUT: usize = 100_000;
+
+static PRESENT: AtomicBool = AtomicBool::new(false);
+static PACKET_SIZE: AtomicUsize = AtomicUsize::new(3);
+
+/// The state of the mouse buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseButtons {
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+}
+
+/// One packet from the mouse: the movement and wheel turn since the previous
+/// packet, and the buttons held now.
+///
+/// `dy` grows downwards like screen rows; `wheel` is positive when turned towards the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseEvent {
+    pub dx: i16,
+    pub dy: i16,
+    pub wheel: i8,
+    pub buttons: MouseButtons,
+}
+
+/// Collects the bytes the mouse sends into packets of 3 bytes, or 4 with a wheel.
+pub struct PacketDecoder {
+    bytes: [u8; 4],
+    len: usize,
+    size: usize,
+}
+
+impl PacketDecoder {
+    pub fn new(size: usize) -> Self {
+        PacketDecoder { bytes: [0; 4], len: 0, size }
+    }
+
+    /// Adds a byte, returning the event once a packet is complete.
+    ///
+    /// The first byte of every packet has bit 3 set; bytes that cannot start a
+    /// packet are dropped so the decoder finds its way back after a lost byte.
+    pub fn add_byte(&mut self, byte: u8) -> Option<MouseEvent> {
+        if self.len == 0 && byte & 0x08 == 0 {
+            return None;
+        }
+        self.bytes[self.len] = byte;
+        self.len += 1;
+        if self.len < self.size {
+            return None;
+        }
+        self.len = 0;
+        let [flags, x, y, extra] = self.bytes;
+        if flags & 0xc0 != 0 {
+            return None; // the counters overflowed, so the movement is meaningless
+        }
+        // The sign bits of the 9-bit deltas are in the first byte
+        let dx = x as i16 - (((flags & 0x10) as i16) << 4);
+        let dy = y as i16 - (((flags & 0x20) as i16) << 3);
+        let wheel = match self.size {
+            4 => ((extra << 4) as i8) >> 4, // a 4-bit signed value
+            _ => 0,
+        };
+        Some(MouseEvent {
+            dx,
+            dy: -dy,
+            wheel,
+            buttons: MouseButtons {
+                left: flags & 0x01 != 0,
+                right: flags & 0x02 != 0,
+                middle: flags & 0x04 != 0,
+            },
+        })
+    }
+}
+
+/// Whether `init` found a mouse.
+pub fn is_present() -> bool {
+    PRESENT.load(Ordering::Relaxed)
+}
+
+/// Number of bytes in each packet: 4 if the mouse has a wheel, otherwise 3.
+pub fn packet_size() -> usize {
+    PACKET_SIZE.load(Ordering::Relaxed)
+}
+
+/// Enables the PS/2 auxiliary port and the mouse on it, and unmasks IRQ12.
+///
+/// Must run with interrupts disabled, since it reads the mouse's replies from
+/// the data port itself. Without a mouse it gives up and leaves IRQ12 masked.
+pub fn init() {
+    if configure().is_none() {
+        return;
+    }
+    PRESENT.store(true, Ordering::Relaxed);
+    // IRQ12 is on the secondary PIC, which reaches the CPU through IRQ2 on the primary one
+    let mut primary: Port<u8> = Port::new(PIC_1_DATA_PORT);
+    let mut secondary: Port<u8> = Port::new(PIC_2_DATA_PORT);
+    unsafe {
+        let mask = primary.read();
+        primary.write(mask & !(1 << 2));
+        let mask = secondary.read();
+        secondary.write(mask & !(1 << (12 - 8)));
+    }
+}
+
+fn configure() -> Option<()> {
+    write_command(ENABLE_AUX)?;
+    write_command(READ_CONFIG)?;
+    let config = read_data()?;
+    write_command(WRITE_CONFIG)?;
+    write_data((config | CONFIG_AUX_INTERRUPT) & !CONFIG_AUX_CLOCK_DISABLED)?;
+
+    mouse_command(SET_DEFAULTS)?;
+    // This sample rate sequence turns on the wheel of mice that have one
+    for rate in [200, 100, 80] {
+        mouse_command(SET_SAMPLE_RATE)?;
+        mouse_command(rate)?;
+    }
+    mouse_command(GET_DEVICE_ID)?;
+    if read_data()? == ID_WHEEL_MOUSE {
+        PACKET_SIZE.store(4, Ordering::Relaxed);
+    }
+    mouse_command(ENABLE_REPORTING)
+}
+
+/// Sends a byte to the mouse and waits for it to acknowledge.
+fn mouse_command(byte: u8) -> Option<()> {
+    write_command(WRITE_AUX)?;
+    write_data(byte)?;
+    (read_data()? == ACK).then_some(())
+}
+
+fn write_command(command: u8) -> Option<()> {
+    wait_for_status(STATUS_INPUT_FULL, false)?;
+    unsafe { Port::new(STATUS_PORT).write(command) };
+    Some(())
+}
+
+fn write_data(byte: u8) -> Option<()> {
+    wait_for_status(STATUS_INPUT_FULL, false)?;
+    unsafe { Port::new(DATA_PORT).write(byte) };
+    Some(())
+}
+
+fn read_data() -> Option<u8> {
+    wait_for_status(STATUS_OUTPUT_FULL, true)?;
+    Some(unsafe { Port::new(DATA_PORT).read() })
+}
+
+fn wait_for_status(bit: u8, set: bool) -> Option<()> {
+    let mut status: Port<u8> = Port::new(STATUS_PORT);
+    (0..TIMEOUT).find(|_| (unsafe { status.read() } & bit != 0) == set).map(|_| ())
+}
+
+#[test_case]
+fn test_decode_packets() {
+    let mut decoder = PacketDecoder::new(4);
+    assert_eq!(decoder.add_byte(0x00), None); // not a packet start
+    assert_eq!(decoder.add_byte(0x19), None); // left button, negative dx
+    assert_eq!(decoder.add_byte(0xfe), None);
+    assert_eq!(decoder.add_byte(0x05), None);
+    let event = decoder.add_byte(0x0f).unwrap();
+    assert_eq!((event.dx, event.dy, event.wheel), (-2, -5, -1));
+    assert!(event.buttons.left && !event.buttons.right);
+}
@@ -1,4 +1,4 @@
-use crate::{print, println};
+use crate::{log, print};
 use conquer_once::spin::OnceCell;
 use core::{
     pin::Pin,
@@ -6,27 +6,33 @@ use core::{
 };
 use crossbeam_queue::ArrayQueue;
 use futures_util::{
-    stream::{Stream, StreamExt},
+    stream::Stream,
     task::AtomicWaker,
 };
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use pc_keyboard::DecodedKey;
+use crate::keyboard::read_key;
 
-static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+// Each scancode is queued with the console that was on screen when it arrived
+static SCANCODE_QUEUE: OnceCell<ArrayQueue<(usize, u8)>> = OnceCell::uninit();
 static WAKER: AtomicWaker = AtomicWaker::new();
 
 /// Called by the keyboard interrupt handler
 ///
 /// Must not block or allocate.
-pub(crate) fn add_scancode(scancode: u8) {
+pub(crate) fn add_scancode(console: usize, scancode: u8) {
     if let Ok(queue) = SCANCODE_QUEUE.try_get() {
-        if let Err(_) = queue.push(scancode) {
-            println!("WARNING: scancode queue full; dropping keyboard input");
+        if let Err(_) = queue.push((console, scancode)) {
+            log!("WARNING: scancode queue full; dropping keyboard input");
         } else {
             WAKER.wake();
         }
-    } else {
-        println!("WARNING: scancode queue uninitialized");
     }
+    // Keys pressed before anyone reads input are dropped
+}
+
+/// Takes the next queued scancode without waiting, for callers that drain the queue.
+pub(crate) fn take_scancode() -> Option<(usize, u8)> {
+    SCANCODE_QUEUE.try_get().ok().and_then(|queue| queue.pop())
 }
 
 pub struct ScancodeStream {
@@ -34,18 +40,20 @@ pub struct ScancodeStream {
 }
 
 impl ScancodeStream {
+    /// Creates a stream over the scancodes queued by the keyboard interrupt, each
+    /// with the console it was typed on.
+    ///
+    /// There is a single queue and waker, so only one stream may be polled at a time.
     pub fn new() -> Self {
-        SCANCODE_QUEUE
-            .try_init_once(|| ArrayQueue::new(100))
-            .expect("ScancodeStream::new should only be called once");
+        SCANCODE_QUEUE.init_once(|| ArrayQueue::new(100));
         ScancodeStream { _private: () }
     }
 }
 
 impl Stream for ScancodeStream {
-    type Item = u8;
+    type Item = (usize, u8);
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<(usize, u8)>> {
         let queue = SCANCODE_QUEUE
             .try_get()
             .expect("scancode queue not initialized");
@@ -66,22 +74,12 @@ impl Stream for ScancodeStream {
     }
 }
 
+/// Echoes every key press, decoded with the keymap in use.
 pub async fn print_keypresses() {
-    let mut scancodes = ScancodeStream::new();
-    let mut keyboard = Keyboard::new(
-        ScancodeSet1::new(),
-        layouts::Us104Key,
-        HandleControl::Ignore,
-    );
-
-    while let Some(scancode) = scancodes.next().await {
-        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-            if let Some(key) = keyboard.process_keyevent(key_event) {
-                match key {
-                    DecodedKey::Unicode(character) => print!("{}", character),
-                    DecodedKey::RawKey(key) => print!("{:?}", key),
-                }
-            }
+    loop {
+        match read_key().await {
+            DecodedKey::Unicode(character) => print!("{}", character),
+            DecodedKey::RawKey(key) => print!("{:?}", key),
         }
     }
 }
\ No newline at end of file
@@ -1,13 +1,14 @@
-use alloc::boxed::Box;
+use alloc::{boxed::Box, sync::Arc, task::Wake};
 use core::{
     future::Future,
-    pin::Pin,
-    sync::atomic::{AtomicU64, Ordering},
-    task::{Context, Poll},
+    pin::{pin, Pin},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    task::{Context, Poll, Waker},
 };
 
 pub mod executor;
 pub mod keyboard;
+pub mod mouse;
 pub mod simple_executor;
 
 pub struct Task {
@@ -28,6 +29,67 @@ impl Task {
     }
 }
 
+/// Returns a future that is pending once, letting the executor run the other ready tasks first.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref(); // requeue right away, there is nothing to wait for
+        Poll::Pending
+    }
+}
+
+/// Runs `future` to completion on the current stack, halting the CPU while it waits.
+///
+/// The tests use it to await the file operations outside the executor. Other tasks
+/// on the executor do not run meanwhile.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let woken = Arc::new(WokenFlag(AtomicBool::new(true))); // poll once to start
+    let waker = Waker::from(woken.clone());
+    let mut context = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if woken.0.swap(false, Ordering::AcqRel) {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+        // Same check as `Executor::sleep_if_idle`: a wake-up between the check and
+        // `hlt` would otherwise be missed until the next interrupt
+        use x86_64::instructions::interrupts::{self, enable_and_hlt};
+        interrupts::disable();
+        if woken.0.load(Ordering::Acquire) {
+            interrupts::enable();
+        } else {
+            enable_and_hlt();
+        }
+    }
+}
+
+struct WokenFlag(AtomicBool);
+
+impl Wake for WokenFlag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct TaskId(u64);
 
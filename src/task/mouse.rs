@@ -0,0 +1,100 @@
+use crate::mouse::{packet_size, MouseEvent, PacketDecoder};
+use crate::vga_buffer::console;
+use crate::log;
+use conquer_once::spin::OnceCell;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use crossbeam_queue::ArrayQueue;
+use futures_util::{stream::{Stream, StreamExt}, task::AtomicWaker};
+use x86_64::instructions::interrupts;
+
+static MOUSE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+// Lines scrolled per notch of the wheel
+const WHEEL_LINES: isize = 3;
+
+/// Called by the mouse interrupt handler
+///
+/// Must not block or allocate.
+pub(crate) fn add_mouse_byte(byte: u8) {
+    if let Ok(queue) = MOUSE_QUEUE.try_get() {
+        if queue.push(byte).is_err() {
+            log!("WARNING: mouse queue full; dropping mouse input");
+        } else {
+            WAKER.wake();
+        }
+    }
+    // Movement before anyone listens is dropped
+}
+
+/// A stream of the events decoded from the packets the mouse interrupt queues.
+///
+/// Like `ScancodeStream`, there is a single queue and waker, so only one stream
+/// may be polled at a time. The kernel polls one in `scroll_with_wheel`.
+pub struct MouseStream {
+    decoder: PacketDecoder,
+}
+
+impl MouseStream {
+    pub fn new() -> Self {
+        MOUSE_QUEUE.init_once(|| ArrayQueue::new(256));
+        MouseStream { decoder: PacketDecoder::new(packet_size()) }
+    }
+
+    /// Decodes queued bytes until a packet is complete or the queue is empty.
+    fn next_event(&mut self, queue: &ArrayQueue<u8>) -> Option<MouseEvent> {
+        while let Some(byte) = queue.pop() {
+            if let Some(event) = self.decoder.add_byte(byte) {
+                return Some(event);
+            }
+        }
+        None
+    }
+}
+
+impl Default for MouseStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stream for MouseStream {
+    type Item = MouseEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<MouseEvent>> {
+        let queue = MOUSE_QUEUE.try_get().expect("mouse queue not initialized");
+        let stream = self.get_mut();
+
+        // fast path
+        if let Some(event) = stream.next_event(queue) {
+            return Poll::Ready(Some(event));
+        }
+
+        WAKER.register(cx.waker());
+        match stream.next_event(queue) {
+            Some(event) => {
+                WAKER.take();
+                Poll::Ready(Some(event))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Scrolls the console on screen through its scrollback as the mouse wheel turns.
+pub async fn scroll_with_wheel() {
+    let mut events = MouseStream::new();
+    while let Some(event) = events.next().await {
+        if event.wheel == 0 {
+            continue;
+        }
+        // Turning the wheel towards the user scrolls down, towards the newest output
+        let lines = -(event.wheel as isize) * WHEEL_LINES;
+        if let Some(writer) = console::console(console::active()) {
+            interrupts::without_interrupts(|| writer.lock().scroll_view(lines));
+        }
+    }
+}
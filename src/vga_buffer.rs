@@ -1,9 +1,19 @@
 use volatile::Volatile;
+use alloc::vec::Vec;
 use core::fmt;
 use lazy_static::lazy_static;
 use spin::Mutex;
-const BUFFER_HEIGHT: usize = 25;
-const BUFFER_WIDTH: usize = 80;
+use x86_64::instructions::port::Port;
+use self::ansi::{Action, AnsiParser, Params};
+use self::scrollback::Scrollback;
+
+pub(crate) mod ansi; // Contains the parser for the VT100 escape sequences the writer interprets
+pub mod cp437;  // Contains the mapping from Unicode characters to the glyphs of the VGA font
+mod scrollback; // Contains the ring of lines that scrolled off the screen, viewed with Shift+PageUp
+pub mod console; // Contains the virtual consoles of the shell and the kernel log, switched with Alt+F1 and Alt+F2
+
+pub const BUFFER_HEIGHT: usize = 25;
+pub const BUFFER_WIDTH: usize = 80;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -29,14 +39,57 @@ pub enum Color {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
-struct ColorCode(u8); // represent color of the char, first 4 bits are foreground color, next 3 bits is background color, last bit is blink or not
+pub(crate) struct ColorCode(u8); // represent color of the char, first 4 bits are foreground color, next 3 bits is background color, last bit is blink or not
 
 impl ColorCode {
-    fn new(foreground: Color, background: Color) -> ColorCode {
+    pub(crate) const fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    /// The palette index of the text color.
+    pub(crate) fn foreground(self) -> u8 {
+        self.0 & 0x0f
+    }
+
+    /// The palette index of the background color.
+    pub(crate) fn background(self) -> u8 {
+        self.0 >> 4
+    }
+
+    /// The colors after applying SGR parameters.
+    ///
+    /// Bold selects the bright variant of the foreground color. Bright backgrounds
+    /// fall back to the normal ones, since the attribute bit they need makes text blink.
+    pub(crate) fn with_graphics(self, params: &Params) -> ColorCode {
+        if params.is_empty() {
+            return DEFAULT_COLOR;
+        }
+        params.iter().fold(self, |color_code, param| {
+            let (foreground, background) = (color_code.foreground(), color_code.background());
+            let (foreground, background) = match param {
+                0 => (DEFAULT_COLOR.foreground(), DEFAULT_COLOR.background()),
+                1 => (foreground | 0x08, background),
+                22 => (foreground & 0x07, background),
+                7 => (background, foreground & 0x07),
+                30..=37 => (ANSI_TO_VGA[(param - 30) as usize] | (foreground & 0x08), background),
+                39 => (DEFAULT_COLOR.foreground(), background),
+                40..=47 => (foreground, ANSI_TO_VGA[(param - 40) as usize]),
+                49 => (foreground, DEFAULT_COLOR.background()),
+                90..=97 => (ANSI_TO_VGA[(param - 90) as usize] | 0x08, background),
+                100..=107 => (foreground, ANSI_TO_VGA[(param - 100) as usize]),
+                _ => (foreground, background), // underline, blink and the like are not supported
+            };
+            ColorCode(background << 4 | foreground)
+        })
+    }
 }
 
+// Colors of text printed before any escape sequence changes them, and after `ESC [ 0 m`
+pub(crate) const DEFAULT_COLOR: ColorCode = ColorCode::new(Color::Yellow, Color::Black);
+
+// VGA palette indexes of the eight ANSI colors: black, red, green, yellow, blue, magenta, cyan, white
+const ANSI_TO_VGA: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
 struct ScreenChar { 
@@ -51,8 +104,43 @@ struct Buffer {
 }
 pub struct Writer {
     column_position: usize,
+    row_position: usize, // the bottom row unless moved with set_position
     color_code: ColorCode,
     buffer: &'static mut Buffer,
+    scrollback: Option<Scrollback>, // None until the heap exists
+    cursor_shape: CursorShape,
+    cursor_visible: bool,
+    ansi: AnsiParser,
+    saved_cursor: (usize, usize, ColorCode), // row, column and colors kept by ESC 7
+    visible: bool,                           // whether `buffer` is the VGA memory
+    off_screen: Option<&'static mut Buffer>, // where the text goes while another console is shown
+}
+
+// CRT controller ports and the cursor registers behind them
+const CRTC_INDEX_PORT: u16 = 0x3d4;
+const CRTC_DATA_PORT: u16 = 0x3d5;
+const CRTC_CURSOR_START: u8 = 0x0a;
+const CRTC_CURSOR_END: u8 = 0x0b;
+const CRTC_CURSOR_HIGH: u8 = 0x0e;
+const CRTC_CURSOR_LOW: u8 = 0x0f;
+const CURSOR_DISABLED: u8 = 0x20; // bit in the cursor start register
+
+/// Which scanlines of the character cell the hardware cursor covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Underline,
+    Block,
+    Scanlines(u8, u8), // first and last scanline, 0 to 15
+}
+
+impl CursorShape {
+    fn scanlines(self) -> (u8, u8) {
+        match self {
+            CursorShape::Underline => (14, 15),
+            CursorShape::Block => (0, 15),
+            CursorShape::Scanlines(start, end) => (start & 0x1f, end & 0x1f),
+        }
+    }
 }
 
 #[macro_export]
@@ -77,6 +165,12 @@ pub fn _print(args: fmt::Arguments) {
 
 impl Writer {
     pub fn write_byte(&mut self, byte: u8) {
+        self.put_byte(byte);
+        self.update_cursor();
+    }
+
+    fn put_byte(&mut self, byte: u8) {
+        self.snap_to_bottom();
         match byte {
             b'\n' => self.new_line(),
             b'\t' => self.tab(),
@@ -86,7 +180,7 @@ impl Writer {
                     self.column_position -= 1;
                     
                     // Clear the character at current position
-                    let row = BUFFER_HEIGHT - 1;
+                    let row = self.row_position;
                     let col = self.column_position;
                     
                     self.buffer.chars[row][col].write(ScreenChar {
@@ -97,34 +191,99 @@ impl Writer {
                 // Optional: Handle backspace at beginning of line if needed
                 // This would require more complex line management
             },
-            byte => {
-                if self.column_position >= BUFFER_WIDTH {
-                    self.new_line();
-                }
-    
-                let row = BUFFER_HEIGHT - 1;
-                let col = self.column_position;
-    
-                let color_code = self.color_code;
-                self.buffer.chars[row][col].write(ScreenChar {
-                    ascii_character: byte,
-                    color_code,
-                });
-                self.column_position += 1;
-            }
+            byte => self.put_glyph(byte),
+        }
+    }
+
+    /// Writes the code page 437 glyph `byte` at the insertion point, even if it is a control character.
+    fn put_glyph(&mut self, byte: u8) {
+        self.snap_to_bottom();
+        if self.column_position >= BUFFER_WIDTH {
+            self.new_line();
         }
+
+        let row = self.row_position;
+        let col = self.column_position;
+
+        let color_code = self.color_code;
+        self.buffer.chars[row][col].write(ScreenChar {
+            ascii_character: byte,
+            color_code,
+        });
+        self.column_position += 1;
     }
 
 }
 
 impl Writer {
+    /// Writes `s`, acting on the VT100 escape sequences in it and showing other
+    /// characters with their code page 437 glyphs.
     pub fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
+        for character in s.chars() {
+            if !character.is_ascii() {
+                // Escape sequences are all ASCII, so anything else inside one is dropped
+                if self.ansi.is_idle() {
+                    self.put_glyph(cp437::encode(character));
+                }
+                continue;
+            }
+            match self.ansi.advance(character as u8) {
                 // printable ASCII byte, newline, tab, or backspace
-                0x20..=0x7e | b'\n' | b'\t' | b'\x08' => self.write_byte(byte),
-                // not part of printable ASCII range
-                _ => self.write_byte(0xfe),
+                Some(Action::Print(byte @ (0x20..=0x7e | b'\n' | b'\t' | b'\x08'))) => self.put_byte(byte),
+                // other control characters
+                Some(Action::Print(_)) => self.put_glyph(cp437::REPLACEMENT),
+                Some(action) => self.apply(action),
+                None => {}
+            }
+        }
+        self.update_cursor();
+    }
+
+    /// Carries out a control sequence. Cursor movement stops at the screen edges.
+    fn apply(&mut self, action: Action) {
+        self.snap_to_bottom();
+        let (row, col) = (self.row_position, self.column_position);
+        match action {
+            Action::Print(byte) => self.put_byte(byte),
+            Action::CursorUp(count) => self.row_position = row.saturating_sub(count),
+            Action::CursorDown(count) => self.row_position = (row + count).min(BUFFER_HEIGHT - 1),
+            Action::CursorForward(count) => self.column_position = (col + count).min(BUFFER_WIDTH - 1),
+            Action::CursorBack(count) => self.column_position = col.min(BUFFER_WIDTH - 1).saturating_sub(count),
+            Action::CursorTo(row, col) => {
+                self.row_position = row.min(BUFFER_HEIGHT - 1);
+                self.column_position = col.min(BUFFER_WIDTH - 1);
+            }
+            Action::EraseDisplay(mode) => {
+                let (rows_before, rows_after) = match mode {
+                    0 => (row + 1..row + 1, row + 1..BUFFER_HEIGHT),
+                    1 => (0..row, row..row),
+                    _ => (0..BUFFER_HEIGHT, row..row),
+                };
+                for other in rows_before.chain(rows_after) {
+                    self.clear_row(other);
+                }
+                if mode < 2 {
+                    self.apply(Action::EraseLine(mode));
+                }
+            }
+            Action::EraseLine(mode) => {
+                let cols = match mode {
+                    0 => col..BUFFER_WIDTH,
+                    1 => 0..(col + 1).min(BUFFER_WIDTH),
+                    _ => 0..BUFFER_WIDTH,
+                };
+                let blank = ScreenChar { ascii_character: b' ', color_code: self.color_code };
+                for other in cols {
+                    self.buffer.chars[row][other].write(blank);
+                }
+            }
+            Action::SetGraphics(params) => self.color_code = self.color_code.with_graphics(&params),
+            Action::SaveCursor => self.saved_cursor = (row, col, self.color_code),
+            Action::RestoreCursor => {
+                let (row, col, color_code) = self.saved_cursor;
+                self.row_position = row;
+                self.column_position = col;
+                self.color_code = color_code;
             }
         }
     }
@@ -138,6 +297,15 @@ impl fmt::Write for Writer {
 }
 impl Writer {
     fn new_line(&mut self) {
+        self.column_position = 0;
+        // Above the bottom row the next line is already on screen
+        if self.row_position < BUFFER_HEIGHT - 1 {
+            self.row_position += 1;
+            return;
+        }
+        if let Some(scrollback) = &mut self.scrollback {
+            scrollback.push(core::array::from_fn(|col| self.buffer.chars[0][col].read()));
+        }
         for row in 1..BUFFER_HEIGHT {
             for col in 0..BUFFER_WIDTH {
                 let character = self.buffer.chars[row][col].read();
@@ -145,11 +313,181 @@ impl Writer {
             }
         }
         self.clear_row(BUFFER_HEIGHT - 1);
-        self.column_position = 0;
     }
+    /// Returns the column the next character will be written to.
+    pub fn column(&self) -> usize {
+        self.column_position
+    }
+
+    /// Moves the insertion point within the current line without changing its text.
+    pub fn set_column(&mut self, col: usize) {
+        self.column_position = col.min(BUFFER_WIDTH - 1);
+        self.update_cursor();
+    }
+
+    /// Returns the row and column the next character will be written to.
+    pub fn position(&self) -> (usize, usize) {
+        (self.row_position, self.column_position)
+    }
+
+    /// Moves the insertion point anywhere on the screen. Output continues from there,
+    /// moving down a row at each line break until it reaches the bottom and scrolls.
+    pub fn set_position(&mut self, row: usize, col: usize) {
+        self.row_position = row.min(BUFFER_HEIGHT - 1);
+        self.set_column(col);
+    }
+
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.cursor_shape = shape;
+        self.apply_cursor_shape();
+    }
+
+    /// Shows or hides the blinking hardware cursor.
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+        self.apply_cursor_shape();
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// Moves the hardware cursor to the insertion point.
+    fn update_cursor(&mut self) {
+        if !self.visible {
+            return;
+        }
+        let position = (self.row_position * BUFFER_WIDTH + self.column_position.min(BUFFER_WIDTH - 1)) as u16;
+        write_crtc(CRTC_CURSOR_HIGH, (position >> 8) as u8);
+        write_crtc(CRTC_CURSOR_LOW, position as u8);
+    }
+
+    /// Programs the cursor scanlines, hiding the cursor while it is off or the view is scrolled back.
+    fn apply_cursor_shape(&mut self) {
+        if !self.visible {
+            return;
+        }
+        let (start, end) = self.cursor_shape.scanlines();
+        let scrolled = self.scrollback.as_ref().is_some_and(Scrollback::is_scrolled);
+        let disabled = if self.cursor_visible && !scrolled { 0 } else { CURSOR_DISABLED };
+        write_crtc(CRTC_CURSOR_START, start | disabled);
+        write_crtc(CRTC_CURSOR_END, end);
+    }
+
+    /// Blanks the current line from the insertion point to the right edge.
+    pub fn clear_to_end_of_line(&mut self) {
+        self.snap_to_bottom();
+        let blank = ScreenChar {
+            ascii_character: b' ',
+            color_code: self.color_code,
+        };
+        for col in self.column_position..BUFFER_WIDTH {
+            self.buffer.chars[self.row_position][col].write(blank);
+        }
+    }
+
+    /// Blanks the whole screen and moves the insertion point to the top left corner.
+    pub fn clear_screen(&mut self) {
+        self.snap_to_bottom();
+        for row in 0..BUFFER_HEIGHT {
+            self.clear_row(row);
+        }
+        self.set_position(0, 0);
+    }
+
+    /// Writes `byte` at an arbitrary position, for full-screen programs.
+    /// `byte` is a code page 437 glyph, as returned by `cp437::encode`.
+    pub fn write_at(&mut self, row: usize, col: usize, byte: u8, foreground: Color, background: Color) {
+        self.snap_to_bottom();
+        self.buffer.chars[row][col].write(ScreenChar {
+            ascii_character: byte,
+            color_code: ColorCode::new(foreground, background),
+        });
+    }
+
+    /// Copies the screen contents and the insertion point so they can be put back later.
+    pub fn save_screen(&mut self) -> SavedScreen {
+        self.snap_to_bottom();
+        let chars = self.buffer.chars.iter().flat_map(|row| row.iter().map(|c| c.read())).collect();
+        SavedScreen { chars, column_position: self.column_position, row_position: self.row_position }
+    }
+
+    pub fn restore_screen(&mut self, saved: &SavedScreen) {
+        self.snap_to_bottom();
+        for (index, character) in saved.chars.iter().enumerate() {
+            self.buffer.chars[index / BUFFER_WIDTH][index % BUFFER_WIDTH].write(*character);
+        }
+        self.set_position(saved.row_position, saved.column_position);
+    }
+
+    /// Scrolls the view `lines` back into the scrollback, or forward if negative.
+    /// Writing anything returns the view to the bottom.
+    pub fn scroll_view(&mut self, lines: isize) {
+        let Some(scrollback) = &mut self.scrollback else {
+            return;
+        };
+        let buffer = &self.buffer;
+        let screen = || (0..BUFFER_HEIGHT).map(|row| core::array::from_fn(|col| buffer.chars[row][col].read())).collect();
+        if !scrollback.scroll(lines, screen) {
+            return;
+        }
+        if !scrollback.is_scrolled() {
+            self.snap_to_bottom_unchecked();
+            return;
+        }
+        for (row, cells) in self.buffer.chars.iter_mut().enumerate() {
+            for (cell, &character) in cells.iter_mut().zip(scrollback.view_row(row)) {
+                cell.write(character);
+            }
+        }
+        self.apply_cursor_shape();
+    }
+
+    /// Puts the live screen back if the view is scrolled up.
+    fn snap_to_bottom(&mut self) {
+        if self.scrollback.as_ref().is_some_and(Scrollback::is_scrolled) {
+            self.snap_to_bottom_unchecked();
+        }
+    }
+
+    fn snap_to_bottom_unchecked(&mut self) {
+        let Some(scrollback) = &mut self.scrollback else {
+            return;
+        };
+        for (cells, line) in self.buffer.chars.iter_mut().zip(scrollback.snap_to_bottom()) {
+            for (cell, character) in cells.iter_mut().zip(line) {
+                cell.write(character);
+            }
+        }
+        self.apply_cursor_shape();
+    }
+
+    /// Moves the text into the off-screen buffer so another console can be shown.
+    fn hide(&mut self) {
+        self.snap_to_bottom();
+        let Some(off_screen) = self.off_screen.take() else {
+            return;
+        };
+        copy_buffer(self.buffer, off_screen);
+        self.buffer = off_screen;
+        self.visible = false;
+    }
+
+    /// Puts the text back on the screen, along with this console's cursor.
+    fn show(&mut self) {
+        let vga = vga_memory();
+        copy_buffer(self.buffer, vga);
+        self.off_screen = Some(core::mem::replace(&mut self.buffer, vga));
+        self.visible = true;
+        self.apply_cursor_shape();
+        self.update_cursor();
+    }
+
     fn tab(&mut self)
     {
-        self.write_string("    ");
+        for _ in 0..4 {
+            self.put_byte(b' ');
+        }
     }
 
     fn clear_row(&mut self, row: usize) {
@@ -163,12 +501,55 @@ impl Writer {
     }
 }
 
+/// Screen contents returned by `Writer::save_screen`.
+pub struct SavedScreen {
+    chars: Vec<ScreenChar>,
+    column_position: usize,
+    row_position: usize,
+}
+
+impl Writer {
+    fn new(buffer: &'static mut Buffer, visible: bool) -> Writer {
+        Writer {
+            column_position: 0,
+            row_position: BUFFER_HEIGHT - 1,
+            color_code: DEFAULT_COLOR,
+            buffer,
+            scrollback: None,
+            cursor_shape: CursorShape::Underline,
+            cursor_visible: true,
+            ansi: AnsiParser::new(),
+            saved_cursor: (BUFFER_HEIGHT - 1, 0, DEFAULT_COLOR),
+            visible,
+            off_screen: None,
+        }
+    }
+}
+
 lazy_static! {
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_position: 0,
-        color_code: ColorCode::new(Color::Yellow, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    });
+    /// The console the shell runs on, shown at boot.
+    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer::new(vga_memory(), true));
+}
+
+fn vga_memory() -> &'static mut Buffer {
+    unsafe { &mut *(0xb8000 as *mut Buffer) }
+}
+
+fn copy_buffer(from: &Buffer, to: &mut Buffer) {
+    for row in 0..BUFFER_HEIGHT {
+        for col in 0..BUFFER_WIDTH {
+            to.chars[row][col].write(from.chars[row][col].read());
+        }
+    }
+}
+
+fn write_crtc(register: u8, value: u8) {
+    let mut index: Port<u8> = Port::new(CRTC_INDEX_PORT);
+    let mut data: Port<u8> = Port::new(CRTC_DATA_PORT);
+    unsafe {
+        index.write(register);
+        data.write(value);
+    }
 }
 
 #[test_case]
@@ -196,4 +577,17 @@ fn test_println_output() {
 #[test_case]
 fn test_println() {
     println!("test_println output");
+}
+#[test_case]
+fn test_escape_sequences() {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+    interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writeln!(writer, "\n\x1b[31mred\x1b[0m plain\x1b[3D!").expect("writeln failed");
+        let row = &writer.buffer.chars[BUFFER_HEIGHT - 2];
+        assert_eq!(row[0].read().color_code, ColorCode::new(Color::Red, Color::Black));
+        assert_eq!(row[4].read().color_code, DEFAULT_COLOR);
+        assert_eq!(row[6].read().ascii_character, b'!'); // written over "plain" three columns back
+    });
 }
\ No newline at end of file
@@ -0,0 +1,156 @@
+/// Most parameters a control sequence may carry; later ones are ignored.
+const MAX_PARAMS: usize = 8;
+
+/// The numeric parameters of a control sequence, like `1;31` in `ESC [ 1 ; 31 m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Params {
+    values: [u16; MAX_PARAMS],
+    len: usize,
+}
+
+impl Params {
+    const fn new() -> Self {
+        Params { values: [0; MAX_PARAMS], len: 0 }
+    }
+
+    /// The parameter at `index`, or `default` if it is missing or 0.
+    pub fn get(&self, index: usize, default: u16) -> u16 {
+        match self.values[..self.len].get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        self.values[..self.len].iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// What the writer should do for the bytes fed to `AnsiParser` so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action {
+    Print(u8),               // a byte to write as before: a character, `\n`, `\t` or backspace
+    CursorUp(usize),         // CUU, ESC [ n A
+    CursorDown(usize),       // CUD, ESC [ n B
+    CursorForward(usize),    // CUF, ESC [ n C
+    CursorBack(usize),       // CUB, ESC [ n D
+    CursorTo(usize, usize),  // CUP, ESC [ row ; col H, zero-based here
+    EraseDisplay(u16),       // ED, ESC [ n J: 0 to the end, 1 from the start, 2 all
+    EraseLine(u16),          // EL, ESC [ n K: 0 to the end, 1 from the start, 2 all
+    SetGraphics(Params),     // SGR, ESC [ ... m
+    SaveCursor,              // ESC 7 or ESC [ s
+    RestoreCursor,           // ESC 8 or ESC [ u
+}
+
+enum State {
+    Ground,
+    Escape,                  // after ESC
+    Csi(Params),             // after ESC [, collecting parameters
+}
+
+/// Splits console output into characters and VT100 control sequences.
+///
+/// Sequences it does not know are swallowed whole rather than printed.
+pub(crate) struct AnsiParser {
+    state: State,
+}
+
+impl AnsiParser {
+    pub const fn new() -> Self {
+        AnsiParser { state: State::Ground }
+    }
+
+    /// Whether the parser is between sequences, so a character would be printed.
+    pub fn is_idle(&self) -> bool {
+        matches!(self.state, State::Ground)
+    }
+
+    /// Feeds one byte, returning what to do once it completes a character or sequence.
+    pub fn advance(&mut self, byte: u8) -> Option<Action> {
+        match &mut self.state {
+            State::Ground => match byte {
+                0x1b => {
+                    self.state = State::Escape;
+                    None
+                }
+                _ => Some(Action::Print(byte)),
+            },
+            State::Escape => {
+                self.state = State::Ground;
+                match byte {
+                    b'[' => {
+                        self.state = State::Csi(Params::new());
+                        None
+                    }
+                    b'7' => Some(Action::SaveCursor),
+                    b'8' => Some(Action::RestoreCursor),
+                    _ => None,
+                }
+            }
+            State::Csi(params) => match byte {
+                b'0'..=b'9' => {
+                    if params.len == 0 {
+                        params.len = 1;
+                    }
+                    if let Some(value) = params.values.get_mut(params.len - 1) {
+                        *value = value.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    }
+                    None
+                }
+                b';' => {
+                    // An empty parameter before the separator counts as 0
+                    params.len = (params.len.max(1) + 1).min(MAX_PARAMS + 1);
+                    None
+                }
+                0x40..=0x7e => {
+                    let mut params = *params;
+                    params.len = params.len.min(MAX_PARAMS);
+                    self.state = State::Ground;
+                    let count = params.get(0, 1) as usize;
+                    match byte {
+                        b'A' => Some(Action::CursorUp(count)),
+                        b'B' => Some(Action::CursorDown(count)),
+                        b'C' => Some(Action::CursorForward(count)),
+                        b'D' => Some(Action::CursorBack(count)),
+                        b'H' | b'f' => Some(Action::CursorTo(count - 1, params.get(1, 1) as usize - 1)),
+                        b'J' => Some(Action::EraseDisplay(params.get(0, 0))),
+                        b'K' => Some(Action::EraseLine(params.get(0, 0))),
+                        b'm' => Some(Action::SetGraphics(params)),
+                        b's' => Some(Action::SaveCursor),
+                        b'u' => Some(Action::RestoreCursor),
+                        _ => None,
+                    }
+                }
+                _ => None, // intermediate and private-marker bytes like `?` are skipped
+            },
+        }
+    }
+}
+
+#[test_case]
+fn test_parse_sequences() {
+    let mut parser = AnsiParser::new();
+    let mut feed = |bytes: &[u8]| {
+        let mut last = None;
+        for &byte in bytes {
+            last = parser.advance(byte);
+        }
+        last
+    };
+    assert_eq!(feed(b"x"), Some(Action::Print(b'x')));
+    assert_eq!(feed(b"\x1b[3A"), Some(Action::CursorUp(3)));
+    assert_eq!(feed(b"\x1b[D"), Some(Action::CursorBack(1)));
+    assert_eq!(feed(b"\x1b[5;10H"), Some(Action::CursorTo(4, 9)));
+    assert_eq!(feed(b"\x1b[;2H"), Some(Action::CursorTo(0, 1)));
+    assert_eq!(feed(b"\x1b[2J"), Some(Action::EraseDisplay(2)));
+    let Some(Action::SetGraphics(params)) = feed(b"\x1b[1;31m") else {
+        panic!("expected SGR");
+    };
+    assert!(params.iter().eq([1, 31]));
+    assert_eq!(feed(b"\x1b[?25l"), None);
+    assert_eq!(feed(b"\x1b7"), Some(Action::SaveCursor));
+}
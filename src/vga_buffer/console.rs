@@ -0,0 +1,106 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use conquer_once::spin::OnceCell;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+use volatile::Volatile;
+use super::{Buffer, Color, ColorCode, ScreenChar, Scrollback, Writer, DEFAULT_COLOR, WRITER};
+
+/// Number of virtual consoles: the shell's on Alt+F1 and the kernel log on Alt+F2.
+///
+/// Only the shell reads keys, so there are no consoles beyond these two.
+pub const CONSOLE_COUNT: usize = 2;
+/// The console `WRITER` writes to and the shell reads its keys from.
+pub const SHELL_CONSOLE: usize = 0;
+/// The console kernel messages printed with `log!` go to.
+pub const LOG_CONSOLE: usize = CONSOLE_COUNT - 1;
+
+// Consoles other than the shell's, which need the heap for their buffers
+static CONSOLES: OnceCell<Vec<Mutex<Writer>>> = OnceCell::uninit();
+static ACTIVE: AtomicUsize = AtomicUsize::new(SHELL_CONSOLE);
+
+/// Creates the log console and starts keeping scrollback on both consoles. Call once the
+/// heap is initialized; until then only the shell console exists.
+pub fn init() {
+    use x86_64::instructions::interrupts;
+    interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writer.scrollback = Some(Scrollback::new());
+        writer.off_screen = Some(new_buffer());
+    });
+    CONSOLES.init_once(|| {
+        (1..CONSOLE_COUNT)
+            .map(|_| {
+                let mut writer = Writer::new(new_buffer(), false);
+                writer.scrollback = Some(Scrollback::new());
+                Mutex::new(writer)
+            })
+            .collect()
+    });
+}
+
+fn new_buffer() -> &'static mut Buffer {
+    let blank = ScreenChar { ascii_character: b' ', color_code: DEFAULT_COLOR };
+    Box::leak(Box::new(Buffer {
+        chars: core::array::from_fn(|_| core::array::from_fn(|_| Volatile::new(blank))),
+    }))
+}
+
+/// The writer of console `index`, if that console exists yet.
+pub fn console(index: usize) -> Option<&'static Mutex<Writer>> {
+    match index {
+        SHELL_CONSOLE => Some(&WRITER),
+        _ => CONSOLES.try_get().ok()?.get(index - 1),
+    }
+}
+
+/// The console on screen.
+pub fn active() -> usize {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Shows console `index`, keeping the text of the one on screen in its off-screen buffer.
+///
+/// Called when Alt+F1 or Alt+F2 is pressed, possibly after a panic, so it gives up rather than wait if either
+/// console is being written to. Returns whether the console was switched.
+pub fn switch_to(index: usize) -> bool {
+    let current = active();
+    // In graphics mode the VGA text memory is not mapped where the writers expect it
+    if index == current || crate::framebuffer::is_active() {
+        return false;
+    }
+    let (Some(from), Some(to)) = (console(current), console(index)) else {
+        return false;
+    };
+    let (Some(mut from), Some(mut to)) = (from.try_lock(), to.try_lock()) else {
+        return false;
+    };
+    from.hide();
+    to.show();
+    ACTIVE.store(index, Ordering::Relaxed);
+    true
+}
+
+/// Writes kernel messages to the log console, or to the shell console before the
+/// log console exists.
+#[doc(hidden)]
+pub fn _log(args: fmt::Arguments) {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+    let writer = console(LOG_CONSOLE).unwrap_or(&WRITER);
+    interrupts::without_interrupts(|| {
+        let mut writer = writer.lock();
+        // Kernel messages stand out in gray from the output of the console's own program
+        let color_code = core::mem::replace(&mut writer.color_code, ColorCode::new(Color::LightGray, Color::Black));
+        writer.write_fmt(args).unwrap();
+        writer.color_code = color_code;
+    });
+}
+
+/// Prints a line to the kernel log console, shown with Alt+F2.
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => ($crate::vga_buffer::console::_log(format_args!("{}\n", format_args!($($arg)*))));
+}
+
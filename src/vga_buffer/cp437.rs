@@ -0,0 +1,57 @@
+/// Glyph shown for characters code page 437 has no equivalent for.
+pub const REPLACEMENT: u8 = 0xfe; // ■
+
+// The glyphs of bytes 0x80 to 0xff, in order
+const HIGH: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»\
+                    ░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀\
+                    αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+// The glyphs of bytes 0x01 to 0x1f, which the writer only shows for these characters
+// since the bytes themselves are control characters
+const LOW: &str = "☺☻♥♦♣♠•◘○◙♂♀♪♫☼►◄↕‼¶§▬↨↑↓→←∟↔▲▼";
+
+// Characters drawn with the glyph of a look-alike
+const ALIASES: [(char, u8); 6] = [
+    ('β', 0xe1), // ß
+    ('μ', 0xe6), // µ, the micro sign
+    ('Ω', 0xea), // U+2126 OHM SIGN
+    ('∑', 0xe4), // Σ
+    ('∈', 0xee), // ε
+    ('⌂', 0x7f),
+];
+
+/// Returns the code page 437 byte that shows `character` on the VGA text console.
+///
+/// ASCII control characters and characters without an equivalent give `REPLACEMENT`.
+pub fn encode(character: char) -> u8 {
+    match character {
+        ' '..='~' => character as u8,
+        _ if character.is_ascii() => REPLACEMENT,
+        _ => lookup(character).unwrap_or(REPLACEMENT),
+    }
+}
+
+fn lookup(character: char) -> Option<u8> {
+    if let Some(index) = HIGH.chars().position(|glyph| glyph == character) {
+        return Some(0x80 + index as u8);
+    }
+    if let Some(index) = LOW.chars().position(|glyph| glyph == character) {
+        return Some(0x01 + index as u8);
+    }
+    ALIASES.iter().find(|&&(alias, _)| alias == character).map(|&(_, byte)| byte)
+}
+
+#[test_case]
+fn test_encode() {
+    assert_eq!(HIGH.chars().count(), 128);
+    assert_eq!(LOW.chars().count(), 31);
+    assert_eq!(encode('A'), b'A');
+    assert_eq!(encode('é'), 0x82);
+    assert_eq!(encode('─'), 0xc4);
+    assert_eq!(encode('╬'), 0xce);
+    assert_eq!(encode('█'), 0xdb);
+    assert_eq!(encode('π'), 0xe3);
+    assert_eq!(encode('♥'), 0x03);
+    assert_eq!(encode('\u{7}'), REPLACEMENT);
+    assert_eq!(encode('€'), REPLACEMENT);
+}
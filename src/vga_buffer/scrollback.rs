@@ -0,0 +1,106 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use super::{ScreenChar, BUFFER_HEIGHT, BUFFER_WIDTH};
+
+/// Number of lines kept after they scroll off the top of the screen.
+///
+/// 500 rows of 160 bytes come to 80 KB for each console that keeps a scrollback,
+/// which the 1 MiB heap can spare.
+pub const SCROLLBACK_LINES: usize = 500;
+
+type Row = [ScreenChar; BUFFER_WIDTH];
+
+/// The lines that scrolled off the screen, and which part of them is on view.
+///
+/// The ring starts empty and grows a screen at a time as lines scroll off.
+///
+/// While the view is scrolled up, the rows that were on screen are kept in
+/// `live` so they can be put back when the view returns to the bottom.
+pub(super) struct Scrollback {
+    lines: VecDeque<Row>, // oldest first
+    live: Vec<Row>,
+    offset: usize,        // lines the view is scrolled up by; 0 shows the live screen
+}
+
+impl Scrollback {
+    pub fn new() -> Self {
+        Scrollback { lines: VecDeque::new(), live: Vec::new(), offset: 0 }
+    }
+
+    /// Keeps a row that is about to scroll off the screen, dropping the oldest one when full.
+    ///
+    /// If the heap cannot spare another screen, the scrollback stops growing instead.
+    pub fn push(&mut self, row: Row) {
+        let len = self.lines.len();
+        let full = len == SCROLLBACK_LINES
+            || (len == self.lines.capacity()
+                && self.lines.try_reserve_exact(BUFFER_HEIGHT.min(SCROLLBACK_LINES - len)).is_err());
+        if full && self.lines.pop_front().is_none() {
+            return; // not even one row fits
+        }
+        self.lines.push_back(row);
+    }
+
+    pub fn is_scrolled(&self) -> bool {
+        self.offset > 0
+    }
+
+    /// Moves the view `lines` up (or down, if negative), saving `screen` when leaving the
+    /// bottom. Returns whether the view changed.
+    pub fn scroll(&mut self, lines: isize, screen: impl FnOnce() -> Vec<Row>) -> bool {
+        let offset = (self.offset as isize + lines).clamp(0, self.lines.len() as isize) as usize;
+        if offset == self.offset {
+            return false;
+        }
+        if self.offset == 0 {
+            self.live = screen();
+        }
+        self.offset = offset;
+        true
+    }
+
+    /// Returns to the bottom, handing back the rows that were on screen.
+    pub fn snap_to_bottom(&mut self) -> Vec<Row> {
+        self.offset = 0;
+        core::mem::take(&mut self.live)
+    }
+
+    /// The row shown at screen row `row` in the current view.
+    pub fn view_row(&self, row: usize) -> &Row {
+        let index = self.lines.len() - self.offset + row;
+        match self.lines.get(index) {
+            Some(line) => line,
+            None => &self.live[index - self.lines.len()],
+        }
+    }
+}
+
+#[test_case]
+fn test_scroll_view() {
+    let blank = ScreenChar { ascii_character: b' ', color_code: super::ColorCode(0) };
+    let row = |byte: u8| [ScreenChar { ascii_character: byte, ..blank }; BUFFER_WIDTH];
+    let mut scrollback = Scrollback::new();
+    for byte in b'a'..=b'c' {
+        scrollback.push(row(byte));
+    }
+    assert!(!scrollback.scroll(-1, Vec::new)); // already at the bottom
+    assert!(scrollback.scroll(2, || (0..BUFFER_HEIGHT).map(|_| row(b'x')).collect()));
+    assert_eq!(scrollback.view_row(0)[0].ascii_character, b'b');
+    assert_eq!(scrollback.view_row(2)[0].ascii_character, b'x');
+    assert!(scrollback.scroll(10, Vec::new)); // stops at the oldest line
+    assert_eq!(scrollback.view_row(0)[0].ascii_character, b'a');
+    assert_eq!(scrollback.snap_to_bottom().len(), BUFFER_HEIGHT);
+    assert!(!scrollback.is_scrolled());
+}
+
+#[test_case]
+fn test_scrollback_grows_up_to_its_limit() {
+    let row = [ScreenChar { ascii_character: b' ', color_code: super::ColorCode(0) }; BUFFER_WIDTH];
+    let mut scrollback = Scrollback::new();
+    assert_eq!(scrollback.lines.capacity(), 0);
+    for _ in 0..SCROLLBACK_LINES + BUFFER_HEIGHT {
+        scrollback.push(row);
+    }
+    assert_eq!(scrollback.lines.len(), SCROLLBACK_LINES);
+    assert!(scrollback.lines.capacity() < SCROLLBACK_LINES + BUFFER_HEIGHT);
+}